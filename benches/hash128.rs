@@ -1,23 +1,20 @@
-#![feature(test)]
-
-// Benchmarks for 64-bit output
+// Benchmarks for 128-bit output
 
 extern crate hash_bench;
 extern crate rand;
-extern crate test;
 
 use std::hash::Hasher;
+use std::hint::black_box;
 use std::slice::from_raw_parts;
-use test::{black_box, Bencher};
 use rand::random;
 
 use hash_bench::*;
+use hash_bench::bench::Bencher;
 
 const N64: u64 = 100;
 
 macro_rules! hash128_u64 {
     ($fnn:ident, $hash:ident) => {
-        #[bench]
         fn $fnn(b: &mut Bencher) {
             let mut x: u64 = random();
             
@@ -43,7 +40,6 @@ hash128_u64!(hash128_u64_metro, MetroHash128);
 macro_rules! hash128_bytes {
     // hash [u64; L] as a byte sequence N times
     ($fnn:ident, $hash:ident, $L:expr, $N:expr) => {
-        #[bench]
         fn $fnn(b: &mut Bencher) {
             let mut x: [u64; $L] = random();
             
@@ -71,3 +67,10 @@ hash128_bytes!(hash128_bytes_4_metro, MetroHash128, 4, 25);
 
 hash128_bytes!(hash128_bytes_25_metro, MetroHash128, 25, 4);
 // hash128_bytes!(hash128_bytes_25_sea, SeaHasher, 25, 4);
+
+bench_main!(
+    hash128_u64_metro,
+    hash128_bytes_1_metro,
+    hash128_bytes_4_metro,
+    hash128_bytes_25_metro,
+);
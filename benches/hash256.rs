@@ -1,28 +1,111 @@
-#![feature(test)]
-
-// Benchmarks for 64-bit output
+// Benchmarks for 256-bit output
 
 extern crate hash_bench;
 extern crate rand;
-extern crate test;
 
+use std::hint::black_box;
 use std::slice::from_raw_parts;
-use test::{black_box, Bencher};
 use rand::random;
 
 use hash_bench::*;
+use hash_bench::bench::Bencher;
 
 fn k12(bytes: &[u8]) -> Vec<u8> {
     kangaroo_twelve(bytes, "", 32)  // 32 * 8 = 256
 }
 fn k12s(bytes: &[u8]) -> [u8; 32] {
-    hash_bench::k12s(bytes)
+    hash_bench::k12s(bytes, "")
+}
+fn m14(bytes: &[u8]) -> Vec<u8> {
+    hash_bench::m14(bytes, "", 32)
+}
+fn cshake128(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = sp800_185::CShake128::new(b"", b"bench");
+    hasher.update(bytes);
+    let mut output = [0u8; 32];
+    hasher.finalize_xof().squeeze(&mut output);
+    output
+}
+fn kmac128(bytes: &[u8]) -> [u8; 32] {
+    let mut mac = sp800_185::Kmac128::new(b"bench key", b"");
+    mac.update(bytes);
+    let mut output = [0u8; 32];
+    mac.finalize(&mut output);
+    output
+}
+fn tuple_hash128(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = sp800_185::TupleHash128::new(b"");
+    hasher.push(bytes);
+    let mut output = [0u8; 32];
+    hasher.finalize(&mut output);
+    output
+}
+fn parallel_hash128(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = sp800_185::ParallelHash128::new(8192, b"");
+    hasher.update(bytes);
+    let mut output = [0u8; 32];
+    hasher.finalize(&mut output);
+    output
+}
+fn duplex(bytes: &[u8]) -> [u8; 32] {
+    let mut duplex = Duplex::new(b"bench");
+    duplex.absorb(bytes);
+    let mut output = [0u8; 32];
+    duplex.squeeze(&mut output);
+    output
+}
+
+// Absorption into a 168-byte-rate Keccak-p[1600, 12] state, as k12s does:
+// with the state kept as bytes and converted to lanes around each
+// permutation (the sponge before it kept u64 lanes), and with whole
+// little-endian words XORed into the lanes directly.
+const ABSORB_RATE: usize = 168;
+
+fn absorb_byte_array(bytes: &[u8]) -> [u8; 32] {
+    fn permute(state: &mut [u8; 200]) {
+        let mut lanes = [0u64; 25];
+        for (lane, b) in lanes.iter_mut().zip(state.chunks(8)) {
+            *lane = u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
+        }
+        keccak::keccak_p1600(&mut lanes, 12);
+        for (lane, b) in lanes.iter().zip(state.chunks_mut(8)) {
+            b.copy_from_slice(&lane.to_le_bytes());
+        }
+    }
+    
+    let mut state = [0u8; 200];
+    for block in bytes.chunks(ABSORB_RATE) {
+        for (s, b) in state.iter_mut().zip(block) {
+            *s ^= b;
+        }
+        permute(&mut state);
+    }
+    let mut output = [0u8; 32];
+    output.copy_from_slice(&state[0..32]);
+    output
+}
+fn absorb_lanes(bytes: &[u8]) -> [u8; 32] {
+    let mut lanes = [0u64; 25];
+    for block in bytes.chunks(ABSORB_RATE) {
+        let mut words = block.chunks_exact(8);
+        for (lane, b) in lanes.iter_mut().zip(&mut words) {
+            *lane ^= u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]);
+        }
+        for (i, &b) in words.remainder().iter().enumerate() {
+            lanes[block.len() / 8] ^= (b as u64) << (8 * i);
+        }
+        keccak::keccak_p1600(&mut lanes, 12);
+    }
+    let mut output = [0u8; 32];
+    for (b, lane) in output.chunks_mut(8).zip(lanes.iter()) {
+        b.copy_from_slice(&lane.to_le_bytes());
+    }
+    output
 }
 
 macro_rules! hash256_bytes {
     // hash [u64; L] as a byte sequence N times
     ($fnn:ident, $hash:expr, $L:expr, $N:expr) => {
-        #[bench]
         fn $fnn(b: &mut Bencher) {
             let mut x: [u64; $L] = random();
             
@@ -51,6 +134,10 @@ hash256_bytes!(hash256_u64arr_1_k12s, k12s, 1, 100);
 hash256_bytes!(hash256_u64arr_4_k12s, k12s, 4, 25);
 hash256_bytes!(hash256_u64arr_25_k12s, k12s, 25, 4);
 
+hash256_bytes!(hash256_u64arr_1_m14, m14, 1, 100);
+hash256_bytes!(hash256_u64arr_4_m14, m14, 4, 25);
+hash256_bytes!(hash256_u64arr_25_m14, m14, 25, 4);
+
 hash256_bytes!(hash256_u64arr_1_sha2, sha512_trunc256, 1, 100);
 hash256_bytes!(hash256_u64arr_4_sha2, sha512_trunc256, 4, 25);
 hash256_bytes!(hash256_u64arr_25_sha2, sha512_trunc256, 25, 4);
@@ -58,3 +145,186 @@ hash256_bytes!(hash256_u64arr_25_sha2, sha512_trunc256, 25, 4);
 hash256_bytes!(hash256_u64arr_1_sha3, sha3_256, 1, 100);
 hash256_bytes!(hash256_u64arr_4_sha3, sha3_256, 4, 25);
 hash256_bytes!(hash256_u64arr_25_sha3, sha3_256, 25, 4);
+
+hash256_bytes!(hash256_u64arr_1_fips202_sha3, fips202::sha3_256, 1, 100);
+hash256_bytes!(hash256_u64arr_4_fips202_sha3, fips202::sha3_256, 4, 25);
+hash256_bytes!(hash256_u64arr_25_fips202_sha3, fips202::sha3_256, 25, 4);
+
+hash256_bytes!(hash256_u64arr_1_fips202_keccak, fips202::keccak256, 1, 100);
+hash256_bytes!(hash256_u64arr_4_fips202_keccak, fips202::keccak256, 4, 25);
+hash256_bytes!(hash256_u64arr_25_fips202_keccak, fips202::keccak256, 25, 4);
+
+hash256_bytes!(hash256_u64arr_1_cshake, cshake128, 1, 100);
+hash256_bytes!(hash256_u64arr_4_cshake, cshake128, 4, 25);
+hash256_bytes!(hash256_u64arr_25_cshake, cshake128, 25, 4);
+
+hash256_bytes!(hash256_u64arr_1_kmac, kmac128, 1, 100);
+hash256_bytes!(hash256_u64arr_4_kmac, kmac128, 4, 25);
+hash256_bytes!(hash256_u64arr_25_kmac, kmac128, 25, 4);
+
+hash256_bytes!(hash256_u64arr_1_tuple_hash, tuple_hash128, 1, 100);
+hash256_bytes!(hash256_u64arr_4_tuple_hash, tuple_hash128, 4, 25);
+hash256_bytes!(hash256_u64arr_25_tuple_hash, tuple_hash128, 25, 4);
+
+hash256_bytes!(hash256_u64arr_1_duplex, duplex, 1, 100);
+hash256_bytes!(hash256_u64arr_4_duplex, duplex, 4, 25);
+hash256_bytes!(hash256_u64arr_25_duplex, duplex, 25, 4);
+
+macro_rules! hash256_vec {
+    // hash a byte sequence of length L
+    ($fnn:ident, $hash:expr, $L:expr) => {
+        fn $fnn(b: &mut Bencher) {
+            let mut x: Vec<u8> = (0..$L).map(|_| random()).collect();
+            
+            b.iter(|| {
+                x[0] = x[0].wrapping_add(1);  // unique message each time
+                black_box($hash(&x[..]));
+            });
+            b.bytes = $L;
+        }
+    }
+}
+
+// Longer messages, where absorption dominates
+hash256_vec!(hash256_bytes_1k_k12, k12, 1024);
+hash256_vec!(hash256_bytes_8k_k12, k12, 8000);
+hash256_vec!(hash256_bytes_64k_k12, k12, 65536);
+hash256_vec!(hash256_bytes_1k_k12s, k12s, 1024);
+hash256_vec!(hash256_bytes_8k_k12s, k12s, 8000);
+hash256_vec!(hash256_bytes_1k_m14, m14, 1024);
+hash256_vec!(hash256_bytes_8k_m14, m14, 8000);
+hash256_vec!(hash256_bytes_64k_m14, m14, 65536);
+hash256_vec!(hash256_bytes_1k_sha3, sha3_256, 1024);
+hash256_vec!(hash256_bytes_8k_sha3, sha3_256, 8000);
+hash256_vec!(hash256_bytes_64k_sha3, sha3_256, 65536);
+hash256_vec!(hash256_bytes_1k_fips202_sha3, fips202::sha3_256, 1024);
+hash256_vec!(hash256_bytes_8k_fips202_sha3, fips202::sha3_256, 8000);
+hash256_vec!(hash256_bytes_64k_fips202_sha3, fips202::sha3_256, 65536);
+hash256_vec!(hash256_bytes_8k_kmac, kmac128, 8000);
+hash256_vec!(hash256_bytes_64k_kmac, kmac128, 65536);
+hash256_vec!(hash256_bytes_8k_parallel_hash, parallel_hash128, 8000);
+hash256_vec!(hash256_bytes_64k_parallel_hash, parallel_hash128, 65536);
+hash256_vec!(hash256_bytes_1k_duplex, duplex, 1024);
+hash256_vec!(hash256_bytes_8k_duplex, duplex, 8000);
+hash256_vec!(hash256_bytes_64k_duplex, duplex, 65536);
+hash256_vec!(hash256_bytes_1k_absorb_byte_array, absorb_byte_array, 1024);
+hash256_vec!(hash256_bytes_8k_absorb_byte_array, absorb_byte_array, 8000);
+hash256_vec!(hash256_bytes_1k_absorb_lanes, absorb_lanes, 1024);
+hash256_vec!(hash256_bytes_8k_absorb_lanes, absorb_lanes, 8000);
+
+macro_rules! duplex_seal {
+    // encrypt and authenticate a message of length L in place
+    ($fnn:ident, $L:expr) => {
+        fn $fnn(b: &mut Bencher) {
+            let mut x: Vec<u8> = (0..$L).map(|_| random()).collect();
+            let mut keyed = Duplex::new(b"bench");
+            keyed.key(b"0123456789abcdef0123456789abcdef");
+            
+            b.iter(|| {
+                let mut duplex = keyed.clone();
+                black_box(duplex.seal(&mut x));
+            });
+            b.bytes = $L;
+        }
+    }
+}
+
+duplex_seal!(duplex_seal_64, 64);
+duplex_seal!(duplex_seal_1k, 1024);
+duplex_seal!(duplex_seal_64k, 65536);
+
+// K12 on a long message, with leaves processed by the given number of threads
+const LONG: usize = 1 << 22;    // 4 MiB
+
+macro_rules! hash256_long_k12 {
+    ($fnn:ident, $threads:expr) => {
+        fn $fnn(b: &mut Bencher) {
+            let x: Vec<u8> = (0..LONG).map(|_| random()).collect();
+            
+            b.iter(|| {
+                let mut hasher = KangarooTwelve::with_threads($threads);
+                hasher.update(&x);
+                let mut output = [0u8; 32];
+                hasher.finalize("", &mut output);
+                black_box(output);
+            });
+            b.bytes = LONG as u64;
+        }
+    }
+}
+
+hash256_long_k12!(hash256_long_k12_threads_1, 1);
+hash256_long_k12!(hash256_long_k12_threads_2, 2);
+hash256_long_k12!(hash256_long_k12_threads_4, 4);
+hash256_long_k12!(hash256_long_k12_threads_8, 8);
+
+bench_main!(
+    hash256_u64arr_1_keccak,
+    hash256_u64arr_4_keccak,
+    hash256_u64arr_25_keccak,
+    hash256_u64arr_1_k12,
+    hash256_u64arr_4_k12,
+    hash256_u64arr_25_k12,
+    hash256_u64arr_1_k12s,
+    hash256_u64arr_4_k12s,
+    hash256_u64arr_25_k12s,
+    hash256_u64arr_1_m14,
+    hash256_u64arr_4_m14,
+    hash256_u64arr_25_m14,
+    hash256_u64arr_1_sha2,
+    hash256_u64arr_4_sha2,
+    hash256_u64arr_25_sha2,
+    hash256_u64arr_1_sha3,
+    hash256_u64arr_4_sha3,
+    hash256_u64arr_25_sha3,
+    hash256_u64arr_1_fips202_sha3,
+    hash256_u64arr_4_fips202_sha3,
+    hash256_u64arr_25_fips202_sha3,
+    hash256_u64arr_1_fips202_keccak,
+    hash256_u64arr_4_fips202_keccak,
+    hash256_u64arr_25_fips202_keccak,
+    hash256_u64arr_1_cshake,
+    hash256_u64arr_4_cshake,
+    hash256_u64arr_25_cshake,
+    hash256_u64arr_1_kmac,
+    hash256_u64arr_4_kmac,
+    hash256_u64arr_25_kmac,
+    hash256_u64arr_1_tuple_hash,
+    hash256_u64arr_4_tuple_hash,
+    hash256_u64arr_25_tuple_hash,
+    hash256_u64arr_1_duplex,
+    hash256_u64arr_4_duplex,
+    hash256_u64arr_25_duplex,
+    hash256_bytes_1k_k12,
+    hash256_bytes_8k_k12,
+    hash256_bytes_64k_k12,
+    hash256_bytes_1k_k12s,
+    hash256_bytes_8k_k12s,
+    hash256_bytes_1k_m14,
+    hash256_bytes_8k_m14,
+    hash256_bytes_64k_m14,
+    hash256_bytes_1k_sha3,
+    hash256_bytes_8k_sha3,
+    hash256_bytes_64k_sha3,
+    hash256_bytes_1k_fips202_sha3,
+    hash256_bytes_8k_fips202_sha3,
+    hash256_bytes_64k_fips202_sha3,
+    hash256_bytes_8k_kmac,
+    hash256_bytes_64k_kmac,
+    hash256_bytes_8k_parallel_hash,
+    hash256_bytes_64k_parallel_hash,
+    hash256_bytes_1k_duplex,
+    hash256_bytes_8k_duplex,
+    hash256_bytes_64k_duplex,
+    hash256_bytes_1k_absorb_byte_array,
+    hash256_bytes_8k_absorb_byte_array,
+    hash256_bytes_1k_absorb_lanes,
+    hash256_bytes_8k_absorb_lanes,
+    duplex_seal_64,
+    duplex_seal_1k,
+    duplex_seal_64k,
+    hash256_long_k12_threads_1,
+    hash256_long_k12_threads_2,
+    hash256_long_k12_threads_4,
+    hash256_long_k12_threads_8,
+);
@@ -1,23 +1,20 @@
-#![feature(test)]
-
 // Benchmarks for 64-bit output
 
 extern crate hash_bench;
 extern crate rand;
-extern crate test;
 
 use std::hash::Hasher;
+use std::hint::black_box;
 use std::slice::from_raw_parts;
-use test::{black_box, Bencher};
 use rand::random;
 
 use hash_bench::*;
+use hash_bench::bench::Bencher;
 
 const N64: u64 = 100;
 
 macro_rules! hash64_u64 {
     ($fnn:ident, $hash:ident) => {
-        #[bench]
         fn $fnn(b: &mut Bencher) {
             let mut x: u64 = random();
             
@@ -38,13 +35,12 @@ macro_rules! hash64_u64 {
 // Does not depend on whether other bench is *run*. Weird optimisation behaviour?
 hash64_u64!(hash64_u64_metro, MetroHash64);
 hash64_u64!(hash64_u64_sea, SeaHasher);
-// hash64_u64!(hash64_u64_highway, HighwayHash);
+hash64_u64!(hash64_u64_highway, HighwayHash);
 
 
 macro_rules! hash64_bytes {
     // hash [u64; L] as a byte sequence N times
     ($fnn:ident, $hash:ident, $L:expr, $N:expr) => {
-        #[bench]
         fn $fnn(b: &mut Bencher) {
             let mut x: [u64; $L] = random();
             
@@ -80,7 +76,6 @@ hash64_bytes!(hash64_bytes_25_highway, HighwayHash, 25, 4);
 // SeaHash allows usage via a different interface, more optimal?
 macro_rules! hash64_buf_sea {
     ($fnn:ident, $L:expr, $N: expr) => {
-        #[bench]
         fn $fnn(b: &mut Bencher) {
             let mut x: [u64; $L] = random();
             
@@ -101,3 +96,70 @@ macro_rules! hash64_buf_sea {
 hash64_buf_sea!(hash64_buf_1_sea, 1, 100);
 hash64_buf_sea!(hash64_buf_4_sea, 4, 25);
 hash64_buf_sea!(hash64_buf_25_sea, 25, 4);
+
+// HighwayHash batch API versus looping over `hash_64`, for N keys of L words
+const HH_KEY: [u64; 4] = [1, 2, 3, 4];
+
+macro_rules! hash64_many_highway {
+    ($fnn:ident, $fnn_loop:ident, $L:expr, $N:expr) => {
+        fn $fnn(b: &mut Bencher) {
+            let x: Vec<[u64; $L]> = (0..$N).map(|_| random()).collect();
+            let keys: Vec<&[u8]> = x.iter().map(|k| {
+                let p = &k[0] as *const u64 as *const u8;
+                unsafe { from_raw_parts(p, $L * 8) }
+            }).collect();
+            let mut out = [0u64; $N];
+            
+            b.iter(|| {
+                HighwayHash::hash_64_many(HH_KEY, black_box(&keys), &mut out);
+                black_box(&out);
+            });
+            b.bytes = 8 * $L * $N;
+        }
+        
+        fn $fnn_loop(b: &mut Bencher) {
+            let x: Vec<[u64; $L]> = (0..$N).map(|_| random()).collect();
+            let keys: Vec<&[u8]> = x.iter().map(|k| {
+                let p = &k[0] as *const u64 as *const u8;
+                unsafe { from_raw_parts(p, $L * 8) }
+            }).collect();
+            let mut out = [0u64; $N];
+            
+            b.iter(|| {
+                for (out, key) in out.iter_mut().zip(black_box(&keys)) {
+                    *out = HighwayHash::hash_64(HH_KEY, key);
+                }
+                black_box(&out);
+            });
+            b.bytes = 8 * $L * $N;
+        }
+    }
+}
+
+hash64_many_highway!(hash64_many_1_highway, hash64_loop_1_highway, 1, 100);
+hash64_many_highway!(hash64_many_4_highway, hash64_loop_4_highway, 4, 100);
+hash64_many_highway!(hash64_many_8_highway, hash64_loop_8_highway, 8, 100);
+
+bench_main!(
+    hash64_u64_metro,
+    hash64_u64_sea,
+    hash64_u64_highway,
+    hash64_bytes_1_metro,
+    hash64_bytes_1_sea,
+    hash64_bytes_1_highway,
+    hash64_bytes_4_metro,
+    hash64_bytes_4_sea,
+    hash64_bytes_4_highway,
+    hash64_bytes_25_metro,
+    hash64_bytes_25_sea,
+    hash64_bytes_25_highway,
+    hash64_buf_1_sea,
+    hash64_buf_4_sea,
+    hash64_buf_25_sea,
+    hash64_many_1_highway,
+    hash64_loop_1_highway,
+    hash64_many_4_highway,
+    hash64_loop_4_highway,
+    hash64_many_8_highway,
+    hash64_loop_8_highway,
+);
@@ -0,0 +1,379 @@
+//! Statistical benchmark runner, usable on stable Rust
+//! 
+//! A replacement for libtest's `Bencher`: each benchmark is warmed up, an
+//! iteration count is chosen so that a sample takes a useful amount of time,
+//! and many samples are taken. The report gives the mean time per iteration
+//! with a bootstrap confidence interval, the median and standard deviation,
+//! throughput, and samples classified as outliers by Tukey's fences.
+//! 
+//! Bench targets set `harness = false` and list their functions with
+//! `bench_main!`. Run with `cargo bench [-- [options] [filter...]]`; options
+//! are `--samples <n>`, `--warm-up <seconds>` and `--time <seconds>`
+//! (measurement time per benchmark). Without `--bench` (as under
+//! `cargo test`), each benchmark is run once as a smoke test.
+
+use std::cmp::max;
+use std::env;
+use std::hint::black_box;
+use std::process::exit;
+use std::time::{Duration, Instant};
+
+/// Confidence level of reported intervals
+const CONFIDENCE: f64 = 0.95;
+
+/// Number of bootstrap resamples for confidence intervals
+const RESAMPLES: usize = 10000;
+
+/// Runner settings
+#[derive(Clone, Copy, Debug)]
+pub struct Config {
+    /// Time spent running the benchmark before measuring
+    pub warm_up: Duration,
+    /// Target total time of all samples
+    pub measurement: Duration,
+    /// Number of samples
+    pub samples: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            warm_up: Duration::from_millis(500),
+            measurement: Duration::from_secs(2),
+            samples: 50,
+        }
+    }
+}
+
+/// Passed to each benchmark function, as libtest's `Bencher`
+pub struct Bencher {
+    /// Bytes processed per iteration, for throughput reports
+    pub bytes: u64,
+    config: Config,
+    bench_mode: bool,
+    summary: Option<Summary>,
+}
+
+impl Bencher {
+    /// Time `f`, passing its result through `black_box`.
+    /// 
+    /// Should be called exactly once by each benchmark function.
+    pub fn iter<T, F: FnMut() -> T>(&mut self, mut f: F) {
+        if !self.bench_mode {
+            black_box(f());
+            return;
+        }
+        
+        // Warm up with doubling batches, estimating the time per iteration
+        let start = Instant::now();
+        let (mut batch, mut total) = (1u64, 0u64);
+        loop {
+            time(batch, &mut f);
+            total += batch;
+            if start.elapsed() >= self.config.warm_up {
+                break;
+            }
+            batch *= 2;
+        }
+        let per_iter = nanos(start.elapsed()) / total as f64;
+        
+        // Spread the measurement time over the samples
+        let per_sample = nanos(self.config.measurement) / self.config.samples as f64;
+        let iters = max(1, (per_sample / per_iter) as u64);
+        let samples: Vec<f64> = (0..self.config.samples).map(|_| {
+            nanos(time(iters, &mut f)) / iters as f64
+        }).collect();
+        self.summary = Some(Summary::new(&samples));
+    }
+}
+
+fn time<T, F: FnMut() -> T>(iters: u64, f: &mut F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iters {
+        black_box(f());
+    }
+    start.elapsed()
+}
+
+fn nanos(d: Duration) -> f64 {
+    d.as_secs() as f64 * 1e9 + d.subsec_nanos() as f64
+}
+
+/// Counts of samples outside Tukey's fences: mild outliers lie more than 1.5
+/// interquartile ranges beyond a quartile, severe ones more than 3.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Outliers {
+    pub low_severe: usize,
+    pub low_mild: usize,
+    pub high_mild: usize,
+    pub high_severe: usize,
+}
+
+impl Outliers {
+    fn classify(sorted: &[f64]) -> Self {
+        let (q1, q3) = (percentile(sorted, 0.25), percentile(sorted, 0.75));
+        let iqr = q3 - q1;
+        let mut outliers = Outliers::default();
+        for &x in sorted {
+            if x < q1 - 3.0 * iqr {
+                outliers.low_severe += 1;
+            } else if x < q1 - 1.5 * iqr {
+                outliers.low_mild += 1;
+            } else if x > q3 + 3.0 * iqr {
+                outliers.high_severe += 1;
+            } else if x > q3 + 1.5 * iqr {
+                outliers.high_mild += 1;
+            }
+        }
+        outliers
+    }
+    
+    /// Total number of outliers
+    pub fn total(&self) -> usize {
+        self.low_severe + self.low_mild + self.high_mild + self.high_severe
+    }
+}
+
+/// Statistics of the time per iteration over all samples, in nanoseconds
+#[derive(Clone, Debug)]
+pub struct Summary {
+    pub mean: f64,
+    /// Bootstrap confidence interval of the mean
+    pub mean_ci: (f64, f64),
+    pub median: f64,
+    pub std_dev: f64,
+    pub outliers: Outliers,
+    pub samples: usize,
+}
+
+impl Summary {
+    /// Summarise per-iteration times; `samples` must not be empty
+    pub fn new(samples: &[f64]) -> Self {
+        assert!(!samples.is_empty());
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let var = samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>()
+                / (n - 1.0).max(1.0);
+        
+        Summary {
+            mean,
+            mean_ci: bootstrap_mean_ci(samples),
+            median: percentile(&sorted, 0.5),
+            std_dev: var.sqrt(),
+            outliers: Outliers::classify(&sorted),
+            samples: samples.len(),
+        }
+    }
+}
+
+/// Percentile `p` (in 0..=1) of sorted data, interpolating linearly
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p * (sorted.len() - 1) as f64;
+    let (lo, frac) = (rank.floor() as usize, rank.fract());
+    if lo + 1 < sorted.len() {
+        sorted[lo] + frac * (sorted[lo + 1] - sorted[lo])
+    } else {
+        sorted[lo]
+    }
+}
+
+/// Percentile bootstrap confidence interval of the mean
+fn bootstrap_mean_ci(samples: &[f64]) -> (f64, f64) {
+    // fixed seed: reports are reproducible given the samples
+    let mut rng = 0x853c49e6748fea9bu64;
+    let n = samples.len();
+    let mut means: Vec<f64> = (0..RESAMPLES).map(|_| {
+        let mut sum = 0.0;
+        for _ in 0..n {
+            // xorshift64*
+            rng ^= rng >> 12;
+            rng ^= rng << 25;
+            rng ^= rng >> 27;
+            let r = rng.wrapping_mul(0x2545f4914f6cdd1d);
+            sum += samples[(((r >> 32) * n as u64) >> 32) as usize];
+        }
+        sum / n as f64
+    }).collect();
+    means.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let tail = (1.0 - CONFIDENCE) / 2.0;
+    (percentile(&means, tail), percentile(&means, 1.0 - tail))
+}
+
+/// Format a time in nanoseconds with a suitable unit
+fn format_time(ns: f64) -> String {
+    if ns < 1e3 {
+        format!("{:.2} ns", ns)
+    } else if ns < 1e6 {
+        format!("{:.2} µs", ns / 1e3)
+    } else if ns < 1e9 {
+        format!("{:.2} ms", ns / 1e6)
+    } else {
+        format!("{:.2} s", ns / 1e9)
+    }
+}
+
+/// Runs and reports benchmarks; see `bench_main!`
+pub struct Runner {
+    config: Config,
+    bench_mode: bool,
+    filters: Vec<String>,
+}
+
+impl Runner {
+    /// Configure from command-line arguments, as passed by `cargo bench`
+    pub fn from_args() -> Self {
+        let mut runner = Runner {
+            config: Config::default(),
+            bench_mode: false,
+            filters: vec![],
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| -> f64 {
+                args.next().and_then(|v| v.parse().ok()).unwrap_or_else(|| {
+                    eprintln!("{} requires a numeric value", name);
+                    exit(1)
+                })
+            };
+            match arg.as_str() {
+                "--bench" => runner.bench_mode = true,
+                "--samples" => runner.config.samples = max(2, value("--samples") as usize),
+                "--warm-up" => runner.config.warm_up = secs(value("--warm-up")),
+                "--time" => runner.config.measurement = secs(value("--time")),
+                // ignore other libtest options
+                _ if arg.starts_with('-') => (),
+                _ => runner.filters.push(arg),
+            }
+        }
+        runner
+    }
+    
+    /// Run a benchmark function, if it matches the filters, and report
+    pub fn run(&mut self, name: &str, f: fn(&mut Bencher)) {
+        if !self.filters.is_empty() && !self.filters.iter().any(|s| name.contains(s.as_str())) {
+            return;
+        }
+        let mut b = Bencher {
+            bytes: 0,
+            config: self.config,
+            bench_mode: self.bench_mode,
+            summary: None,
+        };
+        f(&mut b);
+        
+        let s = match b.summary {
+            Some(s) => s,
+            None => {
+                println!("test {} ... ok", name);
+                return;
+            }
+        };
+        println!("{:<40} time: [{} {} {}]", name,
+                format_time(s.mean_ci.0), format_time(s.mean), format_time(s.mean_ci.1));
+        let mut line = format!("{:<40} median {}, std. dev. {}", "",
+                format_time(s.median), format_time(s.std_dev));
+        if b.bytes > 0 {
+            line += &format!(", {:.0} MB/s", b.bytes as f64 * 1e3 / s.mean);
+        }
+        println!("{}", line);
+        let o = s.outliers;
+        if o.total() > 0 {
+            println!("{:<40} {} of {} samples are outliers ({} low severe, {} low mild, \
+                    {} high mild, {} high severe)", "",
+                    o.total(), s.samples, o.low_severe, o.low_mild, o.high_mild, o.high_severe);
+        }
+    }
+}
+
+fn secs(s: f64) -> Duration {
+    Duration::from_nanos((s * 1e9) as u64)
+}
+
+/// Define `main` for a bench target with `harness = false`, running the
+/// listed functions, each of type `fn(&mut Bencher)`.
+#[macro_export]
+macro_rules! bench_main {
+    ($($f:ident),* $(,)?) => {
+        fn main() {
+            let mut runner = $crate::bench::Runner::from_args();
+            $(runner.run(stringify!($f), $f);)*
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    #[test]
+    fn percentiles() {
+        let data = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(percentile(&data, 0.0), 1.0);
+        assert_eq!(percentile(&data, 0.5), 3.0);
+        assert_eq!(percentile(&data, 1.0), 5.0);
+        assert_eq!(percentile(&data, 0.25), 2.0);
+        assert_eq!(percentile(&data, 0.125), 1.5);
+        assert_eq!(percentile(&[7.0], 0.3), 7.0);
+    }
+    
+    #[test]
+    fn outliers() {
+        // quartiles 10 and 12: mild fences 7 and 15, severe fences 4 and 18
+        let mut data = vec![10.0, 10.0, 11.0, 11.0, 11.0, 12.0, 12.0, 12.0];
+        data.extend_from_slice(&[1.0, 6.0, 16.0, 30.0]);
+        let summary = Summary::new(&data);
+        assert_eq!(summary.outliers.total(), 4);
+        let mut sorted = data.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let (q1, q3) = (percentile(&sorted, 0.25), percentile(&sorted, 0.75));
+        assert_eq!((q1, q3), (10.0, 12.0));
+        assert_eq!(summary.outliers, Outliers {
+            low_severe: 1,
+            low_mild: 1,
+            high_mild: 1,
+            high_severe: 1,
+        });
+    }
+    
+    #[test]
+    fn summary() {
+        let data: Vec<f64> = (0..100).map(|i| 100.0 + (i % 10) as f64).collect();
+        let s = Summary::new(&data);
+        assert_eq!(s.mean, 104.5);
+        assert_eq!(s.median, 104.5);
+        assert!(s.mean_ci.0 < s.mean && s.mean < s.mean_ci.1);
+        assert!(s.mean_ci.1 - s.mean_ci.0 < 2.0);
+        assert_eq!(s.outliers.total(), 0);
+        
+        let s = Summary::new(&[5.0; 10]);
+        assert_eq!((s.mean, s.std_dev, s.mean_ci), (5.0, 0.0, (5.0, 5.0)));
+    }
+    
+    #[test]
+    fn bencher() {
+        let mut count = 0;
+        let mut b = Bencher {
+            bytes: 0,
+            config: Config::default(),
+            bench_mode: false,
+            summary: None,
+        };
+        b.iter(|| count += 1);
+        assert_eq!(count, 1);
+        assert!(b.summary.is_none());
+        
+        let config = Config {
+            warm_up: Duration::from_millis(10),
+            measurement: Duration::from_millis(20),
+            samples: 10,
+        };
+        let mut b = Bencher { bytes: 0, config, bench_mode: true, summary: None };
+        b.iter(|| black_box(1u64).wrapping_mul(3));
+        let s = b.summary.unwrap();
+        assert_eq!(s.samples, 10);
+        assert!(s.mean > 0.0 && s.mean_ci.0 <= s.mean && s.mean <= s.mean_ci.1);
+    }
+}
@@ -0,0 +1,188 @@
+//! Avalanche bias of round-reduced Keccak-p and HighwayHash.
+//! 
+//! For each round count, random inputs are hashed with and without each
+//! single input bit flipped. For every (input bit, output bit) pair we
+//! estimate the probability `p` that the output bit flips; an ideal function
+//! has `p = 1/2`. The bias `|2p - 1|` is reported as its mean, next to the
+//! mean expected from sampling noise alone, and as its maximum over all
+//! pairs. A round count passes if the maximum is within what sampling noise
+//! explains (a Bonferroni-corrected bound at significance 0.01); sample
+//! counts too small for this test to reject anything are refused.
+//! 
+//! Usage: `cargo run --release --features research --bin avalanche --
+//! [keccak|highway] [samples]`
+
+extern crate hash_bench;
+
+use std::env;
+use std::process::exit;
+
+use hash_bench::HighwayHash;
+use hash_bench::keccak::keccak_p1600;
+
+/// Significance level of the pass/fail test
+const ALPHA: f64 = 0.01;
+
+/// Deterministic generator (SplitMix64), so that runs are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Flip counts for each (input bit, output bit) pair
+struct Counts {
+    out_bits: usize,
+    counts: Vec<u32>,
+    samples: u32,
+}
+
+impl Counts {
+    fn new(in_bits: usize, out_bits: usize) -> Self {
+        Counts { out_bits, counts: vec![0; in_bits * out_bits], samples: 0 }
+    }
+    
+    /// Record the output difference `diff` for flipped input bit `bit`
+    fn add(&mut self, bit: usize, diff: &[u64]) {
+        let row = &mut self.counts[bit * self.out_bits .. (bit + 1) * self.out_bits];
+        for (i, &word) in diff.iter().enumerate() {
+            let mut word = word;
+            while word != 0 {
+                row[64 * i + word.trailing_zeros() as usize] += 1;
+                word &= word - 1;
+            }
+        }
+    }
+    
+    fn report(&self, rounds: usize) {
+        let n = self.samples as f64;
+        let (mut sum, mut max) = (0.0, 0.0f64);
+        for &count in &self.counts {
+            let bias = (2.0 * count as f64 / n - 1.0).abs();
+            sum += bias;
+            max = max.max(bias);
+        }
+        let mean = sum / self.counts.len() as f64;
+        
+        let threshold = threshold(self.counts.len(), self.samples);
+        // for an ideal function the mean bias is sqrt(2 / (pi n))
+        let ideal = (2.0 / (std::f64::consts::PI * n)).sqrt();
+        let verdict = if max <= threshold { "pass" } else { "FAIL" };
+        println!("{:>6} {:>10.5} {:>10.5} {:>10.5} {:>10.5}  {}",
+                rounds, mean, ideal, max, threshold, verdict);
+    }
+}
+
+/// Largest bias explained by sampling noise, for `pairs` (input bit, output
+/// bit) pairs and `samples` samples.
+/// 
+/// |2p - 1| has standard deviation 1/sqrt(n); a Chernoff bound with
+/// Bonferroni correction over all pairs, at significance `ALPHA`, gives the
+/// threshold.
+fn threshold(pairs: usize, samples: u32) -> f64 {
+    (2.0 * (2.0 * pairs as f64 / ALPHA).ln() / samples as f64).sqrt()
+}
+
+/// Exit unless `samples` is enough for the test to reject anything: below
+/// this the threshold is at least 1, the largest possible bias.
+fn check_samples(pairs: usize, samples: u32) {
+    let min = (2.0 * (2.0 * pairs as f64 / ALPHA).ln()).floor() as u32 + 1;
+    if samples < min {
+        eprintln!("at least {} samples are needed (threshold {:.3} >= 1 with {})",
+                min, threshold(pairs, samples), samples);
+        exit(1);
+    }
+}
+
+fn print_header() {
+    println!("{:>6} {:>10} {:>10} {:>10} {:>10}",
+            "rounds", "mean bias", "ideal mean", "max bias", "threshold");
+}
+
+fn keccak(samples: u32) {
+    check_samples(1600 * 1600, samples);
+    println!("Keccak-p[1600, n_r]: 1600 input bits, 1600 output bits, {} samples", samples);
+    print_header();
+    let mut rng = Rng(0);
+    for rounds in 1..25 {
+        let mut counts = Counts::new(1600, 1600);
+        for _ in 0..samples {
+            let mut input = [0u64; 25];
+            for lane in input.iter_mut() {
+                *lane = rng.next_u64();
+            }
+            let mut base = input;
+            keccak_p1600(&mut base, rounds);
+            
+            for bit in 0..1600 {
+                let mut state = input;
+                state[bit / 64] ^= 1 << (bit % 64);
+                keccak_p1600(&mut state, rounds);
+                for (x, y) in state.iter_mut().zip(base.iter()) {
+                    *x ^= y;
+                }
+                counts.add(bit, &state);
+            }
+            counts.samples += 1;
+        }
+        counts.report(rounds);
+    }
+}
+
+fn highway(samples: u32) {
+    check_samples(256 * 64, samples);
+    println!("HighwayHash-64 final permutation rounds: 256 input bits (32-byte message), \
+            64 output bits, {} samples", samples);
+    print_header();
+    let mut rng = Rng(0);
+    let key = [rng.next_u64(), rng.next_u64(), rng.next_u64(), rng.next_u64()];
+    let hash = |message: &[u64; 4], rounds| {
+        let mut bytes = [0u8; 32];
+        for (chunk, word) in bytes.chunks_mut(8).zip(message.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        let mut hasher = HighwayHash::new_key(key);
+        hasher.write(&bytes);
+        hasher.finalize_64_rounds(rounds)
+    };
+    
+    for rounds in 0..11 {
+        let mut counts = Counts::new(256, 64);
+        for _ in 0..samples {
+            let message = [rng.next_u64(), rng.next_u64(), rng.next_u64(), rng.next_u64()];
+            let base = hash(&message, rounds);
+            for bit in 0..256 {
+                let mut flipped = message;
+                flipped[bit / 64] ^= 1 << (bit % 64);
+                counts.add(bit, &[hash(&flipped, rounds) ^ base]);
+            }
+            counts.samples += 1;
+        }
+        counts.report(rounds);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let samples = |default| match args.get(2) {
+        Some(s) => s.parse().unwrap_or_else(|_| {
+            eprintln!("invalid sample count: {}", s);
+            exit(1)
+        }),
+        None => default,
+    };
+    match args.get(1).map(|s| s.as_str()) {
+        Some("keccak") => keccak(samples(100)),
+        Some("highway") => highway(samples(10000)),
+        _ => {
+            eprintln!("usage: avalanche [keccak|highway] [samples]");
+            exit(1);
+        }
+    }
+}
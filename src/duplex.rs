@@ -0,0 +1,365 @@
+// Keccak duplex construction, as described in "Duplexing the sponge" by
+// Bertoni, Daemen, Peeters and Van Assche: https://keccak.team/files/SpongeDuplex.pdf
+//
+// To the extent possible under law, the implementer has waived all copyright
+// and related or neighboring rights to the source code in this file.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! Duplex object on Keccak-p[1600, 12], for transcripts and authenticated
+//! encryption
+
+use std::cmp::min;
+use keccak_p::keccak_p1600;
+use util::ct_eq;
+
+/// Rounds of Keccak-p, as in K12
+const ROUNDS: usize = 12;
+
+/// Rate in bytes; capacity 256 for 128-bit security
+const RATE: usize = 1344 / 8;
+
+/// Bytes of the state erased by `ratchet`
+const RATCHET_LEN: usize = 32;
+
+// Domain separation bytes, one per kind of operation
+const DOMAIN_LABEL: u8 = 0x01;
+const DOMAIN_KEY: u8 = 0x02;
+const DOMAIN_ABSORB: u8 = 0x03;
+const DOMAIN_SQUEEZE: u8 = 0x04;
+const DOMAIN_CRYPT: u8 = 0x05;
+const DOMAIN_RATCHET: u8 = 0x06;
+const DOMAIN_TAG: u8 = 0x07;
+
+/// Keccak duplex object.
+/// 
+/// A sequence of operations is applied to one state; every output depends
+/// on all preceding operations, their kinds and the boundaries between them.
+/// Each operation starts by padding the previous one with its own domain
+/// byte and applying the permutation.
+/// 
+/// As a Fiat–Shamir transcript, `absorb` each message and `squeeze`
+/// challenges. For authenticated encryption, `key` the duplex, `absorb` a
+/// nonce and any associated data, then `seal` or `open` the message; the two
+/// parties must perform the same sequence of operations. A (key, nonce)
+/// pair must not be used for two messages.
+#[derive(Clone)]
+pub struct Duplex {
+    lanes: [u64; 25],
+    // bytes of the current block used by the current operation
+    pos: usize,
+}
+
+impl Duplex {
+    /// Length of tags from `seal`, in bytes
+    pub const TAG_LEN: usize = 16;
+    
+    /// Create a duplex bound to the given protocol `label`
+    pub fn new(label: &[u8]) -> Self {
+        let mut duplex = Duplex { lanes: [0; 25], pos: 0 };
+        duplex.begin(DOMAIN_LABEL);
+        duplex.absorb_bytes(label);
+        duplex
+    }
+    
+    /// Absorb a secret key
+    pub fn key(&mut self, key: &[u8]) {
+        self.begin(DOMAIN_KEY);
+        self.absorb_bytes(key);
+    }
+    
+    /// Absorb public data: a message, nonce or associated data
+    pub fn absorb(&mut self, data: &[u8]) {
+        self.begin(DOMAIN_ABSORB);
+        self.absorb_bytes(data);
+    }
+    
+    /// Fill `output` with output depending on all operations so far
+    pub fn squeeze(&mut self, output: &mut [u8]) {
+        self.begin(DOMAIN_SQUEEZE);
+        self.squeeze_bytes(output);
+    }
+    
+    /// Erase part of the state, so that it cannot be rolled back to recover
+    /// earlier outputs (forward secrecy)
+    pub fn ratchet(&mut self) {
+        self.begin(DOMAIN_RATCHET);
+        for lane in self.lanes[0..RATCHET_LEN / 8].iter_mut() {
+            *lane = 0;
+        }
+        self.pos = RATCHET_LEN;
+    }
+    
+    /// Encrypt `data` in place.
+    /// 
+    /// The ciphertext replaces the keystream in the state, so later output
+    /// authenticates it.
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        self.begin(DOMAIN_CRYPT);
+        self.for_each_lane(data.len(), |lane, offset, bytes| {
+            let p = load(bytes, offset);
+            *lane ^= p;
+            store(*lane, offset, bytes);
+        }, data);
+    }
+    
+    /// Decrypt `data` in place; the inverse of `encrypt`.
+    /// 
+    /// The result is not authenticated; see `open`.
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        self.begin(DOMAIN_CRYPT);
+        self.for_each_lane(data.len(), |lane, offset, bytes| {
+            let c = load(bytes, offset);
+            store(*lane ^ c, offset, bytes);
+            *lane ^= load(bytes, offset);
+        }, data);
+    }
+    
+    /// Encrypt `data` in place and return an authentication tag
+    pub fn seal(&mut self, data: &mut [u8]) -> [u8; Duplex::TAG_LEN] {
+        self.encrypt(data);
+        let mut tag = [0u8; Duplex::TAG_LEN];
+        self.begin(DOMAIN_TAG);
+        self.squeeze_bytes(&mut tag);
+        tag
+    }
+    
+    /// Decrypt `data` in place and check the `tag`, in constant time.
+    /// 
+    /// On failure, `data` is zeroed and false is returned. Either way the
+    /// duplex should not be used for further messages after a failure.
+    pub fn open(&mut self, data: &mut [u8], tag: &[u8]) -> bool {
+        self.decrypt(data);
+        let mut expected = [0u8; Duplex::TAG_LEN];
+        self.begin(DOMAIN_TAG);
+        self.squeeze_bytes(&mut expected);
+        
+        if !ct_eq(&expected, tag) {
+            for byte in data.iter_mut() {
+                *byte = 0;
+            }
+            return false;
+        }
+        true
+    }
+    
+    fn permute(&mut self) {
+        keccak_p1600(&mut self.lanes, ROUNDS);
+        self.pos = 0;
+    }
+    
+    /// End the current operation and start a new one of the given kind
+    fn begin(&mut self, domain: u8) {
+        if self.pos == RATE {
+            self.permute();
+        }
+        let pos = self.pos;
+        self.lanes[pos / 8] ^= (domain as u64) << (8 * (pos % 8));
+        self.lanes[RATE / 8 - 1] ^= 0x80 << 56;
+        self.permute();
+    }
+    
+    /// Call `f` on each lane covering the next `len` bytes of the rate,
+    /// permuting after each full block. `f` receives the lane, the offset
+    /// within it and the corresponding bytes of `data`.
+    fn for_each_lane<F>(&mut self, len: usize, mut f: F, data: &mut [u8])
+        where F: FnMut(&mut u64, usize, &mut [u8])
+    {
+        let mut done = 0;
+        while done < len {
+            if self.pos == RATE {
+                self.permute();
+            }
+            let offset = self.pos % 8;
+            let n = min(8 - offset, len - done);
+            f(&mut self.lanes[self.pos / 8], offset, &mut data[done..done + n]);
+            self.pos += n;
+            done += n;
+        }
+    }
+    
+    fn absorb_bytes(&mut self, data: &[u8]) {
+        let mut done = 0;
+        while done < data.len() {
+            if self.pos == RATE {
+                self.permute();
+            }
+            let offset = self.pos % 8;
+            let n = min(8 - offset, data.len() - done);
+            self.lanes[self.pos / 8] ^= load(&data[done..done + n], offset);
+            self.pos += n;
+            done += n;
+        }
+    }
+    
+    fn squeeze_bytes(&mut self, output: &mut [u8]) {
+        self.for_each_lane(output.len(), |lane, offset, bytes| {
+            store(*lane, offset, bytes);
+        }, output);
+    }
+}
+
+/// Little-endian word with `bytes` at byte `offset`, zero elsewhere
+#[inline]
+fn load(bytes: &[u8], offset: usize) -> u64 {
+    let mut word = [0u8; 8];
+    word[offset..offset + bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(word)
+}
+
+/// Write the bytes of little-endian `word` from `offset` to `bytes`
+#[inline]
+fn store(word: u64, offset: usize, bytes: &mut [u8]) {
+    let len = bytes.len();
+    bytes.copy_from_slice(&word.to_le_bytes()[offset..offset + len]);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    const KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+    
+    fn keyed(nonce: &[u8]) -> Duplex {
+        let mut duplex = Duplex::new(b"duplex test");
+        duplex.key(KEY);
+        duplex.absorb(nonce);
+        duplex
+    }
+    
+    fn challenge(duplex: &mut Duplex) -> [u8; 32] {
+        let mut output = [0u8; 32];
+        duplex.squeeze(&mut output);
+        output
+    }
+    
+    #[test]
+    fn round_trip() {
+        let message: Vec<u8> = (0..1000).map(|j| (j % 251) as u8).collect();
+        for &len in &[0, 1, 7, 8, 9, 167, 168, 169, 336, 1000] {
+            let mut data = message[0..len].to_vec();
+            let mut sender = keyed(b"nonce");
+            sender.absorb(b"associated data");
+            let tag = sender.seal(&mut data);
+            if len >= 8 {
+                assert!(data[..] != message[0..len]);
+            }
+            
+            let mut receiver = keyed(b"nonce");
+            receiver.absorb(b"associated data");
+            assert!(receiver.open(&mut data, &tag), "len {}", len);
+            assert_eq!(data, &message[0..len]);
+            
+            // both sides remain in sync
+            assert_eq!(challenge(&mut sender), challenge(&mut receiver));
+        }
+    }
+    
+    #[test]
+    fn tamper() {
+        let message = b"attack at dawn, unless it rains".to_vec();
+        let mut ciphertext = message.clone();
+        let tag = keyed(b"nonce").seal(&mut ciphertext);
+        
+        let open = |nonce: &[u8], data: &[u8], tag: &[u8]| {
+            let mut data = data.to_vec();
+            let ok = keyed(nonce).open(&mut data, tag);
+            (ok, data)
+        };
+        assert_eq!(open(b"nonce", &ciphertext, &tag), (true, message.clone()));
+        
+        for i in 0..ciphertext.len() {
+            let mut bad = ciphertext.clone();
+            bad[i] ^= 0x01;
+            let (ok, data) = open(b"nonce", &bad, &tag);
+            assert!(!ok);
+            assert!(data.iter().all(|&b| b == 0));
+        }
+        for i in 0..tag.len() {
+            let mut bad = tag;
+            bad[i] ^= 0x80;
+            assert!(!open(b"nonce", &ciphertext, &bad).0);
+        }
+        assert!(!open(b"nonce", &ciphertext, &tag[0..8]).0);
+        assert!(!open(b"nonse", &ciphertext, &tag).0);
+        assert!(!open(b"nonce", &ciphertext[0..10], &tag).0);
+        
+        let mut data = ciphertext.clone();
+        let mut wrong_key = Duplex::new(b"duplex test");
+        wrong_key.key(b"0123456789abcdef0123456789abcdeF");
+        wrong_key.absorb(b"nonce");
+        assert!(!wrong_key.open(&mut data, &tag));
+    }
+    
+    #[test]
+    fn transcript() {
+        let mut a = Duplex::new(b"transcript");
+        a.absorb(b"hello");
+        a.absorb(b"world");
+        let mut b = Duplex::new(b"transcript");
+        b.absorb(b"hello");
+        b.absorb(b"world");
+        assert_eq!(challenge(&mut a), challenge(&mut b));
+        assert_eq!(challenge(&mut a), challenge(&mut b));
+        
+        // operation boundaries, kinds and labels all matter
+        let reference = challenge(&mut a.clone());
+        let variants: [fn(&mut Duplex); 5] = [
+            |d| { d.absorb(b"helloworld"); },
+            |d| { d.absorb(b"hellow"); d.absorb(b"orld"); },
+            |d| { d.key(b"hello"); d.absorb(b"world"); },
+            |d| { d.absorb(b"hello"); d.absorb(b"world"); d.absorb(b""); },
+            |d| { d.absorb(b"hello"); d.ratchet(); d.absorb(b"world"); },
+        ];
+        let mut outputs = vec![challenge(&mut Duplex::new(b"transcript"))];
+        for f in &variants {
+            let mut d = Duplex::new(b"transcript");
+            f(&mut d);
+            outputs.push(challenge(&mut d));
+        }
+        let mut d = Duplex::new(b"transcripts");
+        d.absorb(b"hello");
+        d.absorb(b"world");
+        outputs.push(challenge(&mut d));
+        
+        let mut e = Duplex::new(b"transcript");
+        e.absorb(b"hello");
+        e.absorb(b"world");
+        let mut split = [0u8; 32];
+        e.squeeze(&mut split[0..16]);
+        e.squeeze(&mut split[16..32]);
+        outputs.push(split);
+        
+        for (i, x) in outputs.iter().enumerate() {
+            assert!(*x != reference, "variant {}", i);
+            for y in &outputs[0..i] {
+                assert!(x != y, "variant {}", i);
+            }
+        }
+    }
+    
+    #[test]
+    fn keystream_is_separated() {
+        // encrypting zeros does not reveal what squeeze would output
+        let mut a = keyed(b"nonce");
+        let mut zeros = [0u8; 200];
+        a.encrypt(&mut zeros);
+        let mut b = keyed(b"nonce");
+        let mut output = [0u8; 200];
+        b.squeeze(&mut output);
+        assert!(zeros[..] != output[..]);
+    }
+    
+    #[test]
+    fn ratchet() {
+        let mut a = keyed(b"nonce");
+        let mut b = a.clone();
+        a.ratchet();
+        assert!(challenge(&mut a) != challenge(&mut b));
+        
+        let mut c = keyed(b"nonce");
+        c.ratchet();
+        let mut d = keyed(b"nonce");
+        d.ratchet();
+        assert_eq!(challenge(&mut c), challenge(&mut d));
+    }
+}
@@ -0,0 +1,215 @@
+// SHA-3 and SHAKE, as specified in FIPS 202:
+// https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.202.pdf
+//
+// To the extent possible under law, the implementer has waived all copyright
+// and related or neighboring rights to the source code in this file.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! SHA-3, SHAKE and Keccak-256, built on this crate's Keccak-f[1600].
+//! 
+//! These are independent of the `sha3` and `keccak-hash` crates (which the
+//! benchmarks compare against).
+
+use sponge::{Sponge, SpongeReader};
+
+/// Rounds of Keccak-f[1600]
+const ROUNDS: usize = 24;
+
+macro_rules! fixed_output {
+    ($name:ident, $fn_name:ident, $len:expr, $suffix:expr, $doc:expr) => {
+        #[doc = $doc]
+        #[derive(Clone)]
+        pub struct $name {
+            sponge: Sponge,
+        }
+        
+        impl $name {
+            /// Create a new hasher
+            pub fn new() -> Self {
+                // capacity is twice the output length
+                $name { sponge: Sponge::new(200 - 2 * $len, ROUNDS) }
+            }
+            
+            /// Append `input` to the message
+            pub fn update(&mut self, input: &[u8]) {
+                self.sponge.absorb(input);
+            }
+            
+            /// Finish the message and return the hash
+            pub fn finalize(mut self) -> [u8; $len] {
+                self.sponge.pad($suffix);
+                let mut output = [0u8; $len];
+                self.sponge.squeeze(&mut output);
+                output
+            }
+        }
+        
+        impl Default for $name {
+            fn default() -> Self {
+                $name::new()
+            }
+        }
+        
+        #[doc = $doc]
+        pub fn $fn_name<T: AsRef<[u8]>>(input: T) -> [u8; $len] {
+            let mut hasher = $name::new();
+            hasher.update(input.as_ref());
+            hasher.finalize()
+        }
+    }
+}
+
+fixed_output!(Sha3_224, sha3_224, 28, 0x06, "SHA3-224");
+fixed_output!(Sha3_256, sha3_256, 32, 0x06, "SHA3-256");
+fixed_output!(Sha3_384, sha3_384, 48, 0x06, "SHA3-384");
+fixed_output!(Sha3_512, sha3_512, 64, 0x06, "SHA3-512");
+fixed_output!(Keccak256, keccak256, 32, 0x01,
+        "Keccak-256 as used by Ethereum: SHA3-256 with the original Keccak padding");
+
+macro_rules! shake {
+    ($name:ident, $fn_name:ident, $rate:expr, $doc:expr) => {
+        #[doc = $doc]
+        #[derive(Clone)]
+        pub struct $name {
+            sponge: Sponge,
+        }
+        
+        impl $name {
+            /// Create a new hasher
+            pub fn new() -> Self {
+                $name { sponge: Sponge::new($rate, ROUNDS) }
+            }
+            
+            /// Append `input` to the message
+            pub fn update(&mut self, input: &[u8]) {
+                self.sponge.absorb(input);
+            }
+            
+            /// Finish the message and return a reader for an unbounded
+            /// amount of output.
+            pub fn finalize_xof(mut self) -> ShakeReader {
+                self.sponge.pad(0x1F);
+                ShakeReader::new(self.sponge)
+            }
+        }
+        
+        impl Default for $name {
+            fn default() -> Self {
+                $name::new()
+            }
+        }
+        
+        #[doc = $doc]
+        /// 
+        /// Hashes `input` to `output.len()` bytes written to `output`.
+        pub fn $fn_name<T: AsRef<[u8]>>(input: T, output: &mut [u8]) {
+            let mut hasher = $name::new();
+            hasher.update(input.as_ref());
+            hasher.finalize_xof().squeeze(output);
+        }
+    }
+}
+
+shake!(Shake128, shake128, 1344 / 8, "SHAKE128");
+shake!(Shake256, shake256, 1088 / 8, "SHAKE256");
+
+/// Extendable output of `Shake128` or `Shake256`.
+pub type ShakeReader = SpongeReader;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use keccak_hash::keccak;
+    use sha3;
+    use sha3::Digest;
+    use digest::{Input, ExtendableOutput, XofReader};
+    use util::read_bytes;
+    
+    #[test]
+    fn known_answers() {
+        // Source: NIST example values
+        assert_eq!(sha3_224("")[..], read_bytes("6b4e03423667dbb73b6e15454f0eb1abd4597f9a1b078e3f5b5a6bc7")[..]);
+        assert_eq!(sha3_256("")[..], read_bytes("a7ffc6f8bf1ed76651c14756a061d662
+                f580ff4de43b49fa82d80a4b80f8434a")[..]);
+        assert_eq!(sha3_384("")[..], read_bytes("0c63a75b845e4f7d01107d852e4c2485c51a50aaaa94fc61
+                995e71bbee983a2ac3713831264adb47fb6bd1e058d5f004")[..]);
+        assert_eq!(sha3_512("")[..], read_bytes("a69f73cca23a9ac5c8b567dc185a756e97c982164fe25859
+                e0d1dcc1475c80a615b2123af1f5f94c11e3e9402c3ac558f500199d95b6d3e301758586281dcd26")[..]);
+        assert_eq!(sha3_256("abc")[..], read_bytes("3a985da74fe225b2045c172d6bd390bd
+                855f086e3e9d525b46bfe24511431532")[..]);
+        
+        let mut output = [0u8; 32];
+        shake128("", &mut output);
+        assert_eq!(output[..], read_bytes("7f9c2ba4e88f827d616045507605853e
+                d73b8093f6efbc88eb1a6eacfa66ef26")[..]);
+        let mut output = [0u8; 64];
+        shake256("", &mut output);
+        assert_eq!(output[..], read_bytes("46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762f
+                d75dc4ddd8c0f200cb05019d67b592f6fc821c49479ab48640292eacb3b7c4be")[..]);
+        
+        // Source: Ethereum
+        assert_eq!(keccak256("")[..], read_bytes("c5d2460186f7233c927e7db2dcc703c0
+                e500b653ca82273b7bfad8045d85a470")[..]);
+    }
+    
+    #[test]
+    fn differential() {
+        let data: Vec<u8> = (0..1000).map(|j| (j % 251) as u8).collect();
+        // lengths around each of the rates (72, 104, 136, 144, 168)
+        let lengths = [0, 1, 7, 8, 71, 72, 73, 103, 104, 105, 135, 136, 137, 143, 144, 145,
+                167, 168, 169, 500, 1000];
+        for &len in &lengths {
+            let m = &data[0..len];
+            
+            macro_rules! check {
+                ($ours:ident, $theirs:ty) => {{
+                    let mut hasher = <$theirs>::default();
+                    hasher.input(m);
+                    assert_eq!($ours(m)[..], hasher.result()[..], "{}, len {}", stringify!($ours), len);
+                }}
+            }
+            check!(sha3_224, sha3::Sha3_224);
+            check!(sha3_256, sha3::Sha3_256);
+            check!(sha3_384, sha3::Sha3_384);
+            check!(sha3_512, sha3::Sha3_512);
+            assert_eq!(&keccak256(m)[..], &*keccak(m), "keccak256, len {}", len);
+            
+            macro_rules! check_xof {
+                ($ours:ident, $theirs:ty) => {{
+                    let mut hasher = <$theirs>::default();
+                    hasher.process(m);
+                    let mut expected = [0u8; 400];
+                    hasher.xof_result().read(&mut expected);
+                    let mut output = [0u8; 400];
+                    $ours(m, &mut output);
+                    assert_eq!(output[..], expected[..], "{}, len {}", stringify!($ours), len);
+                }}
+            }
+            check_xof!(shake128, sha3::Shake128);
+            check_xof!(shake256, sha3::Shake256);
+        }
+    }
+    
+    #[test]
+    fn streaming() {
+        let m: Vec<u8> = (0..1000).map(|j| (j % 251) as u8).collect();
+        let mut expected = [0u8; 1000];
+        shake128(&m, &mut expected);
+        for &step in &[1, 7, 167, 168, 169, 1000] {
+            let mut hasher = Sha3_512::new();
+            let mut shake = Shake128::new();
+            for piece in m.chunks(step) {
+                hasher.update(piece);
+                shake.update(piece);
+            }
+            assert_eq!(hasher.finalize()[..], sha3_512(&m)[..], "step {}", step);
+            
+            let mut reader = shake.finalize_xof();
+            let mut output = [0u8; 1000];
+            for piece in output.chunks_mut(step) {
+                reader.squeeze(piece);
+            }
+            assert_eq!(output[..], expected[..], "step {}", step);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright 2017 Diggory Hardy
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0>
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Message authentication using keyed HighwayHash
+
+use highwayhash::HighwayHash;
+use util::ct_eq;
+
+/// Length of tags produced and accepted by `HighwayMac`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagLen {
+    /// 64-bit tags
+    B8,
+    /// 128-bit tags
+    B16,
+    /// 256-bit tags
+    B32,
+}
+
+impl TagLen {
+    /// Length in bytes
+    pub fn bytes(self) -> usize {
+        match self {
+            TagLen::B8 => 8,
+            TagLen::B16 => 16,
+            TagLen::B32 => 32,
+        }
+    }
+}
+
+/// MAC based on the 256-bit output of HighwayHash.
+/// 
+/// Truncated tags are a prefix of the full 32-byte tag. The tag length is
+/// fixed when the `HighwayMac` is created, so `verify` never accepts a
+/// shorter tag than intended.
+#[derive(Clone)]
+pub struct HighwayMac {
+    hasher: HighwayHash,
+    tag_len: TagLen,
+}
+
+impl HighwayMac {
+    /// Create a MAC with the given key and 32-byte tags
+    pub fn new(key: [u64; 4]) -> Self {
+        HighwayMac::with_tag_len(key, TagLen::B32)
+    }
+    
+    /// Create a MAC with the given key and tag length
+    pub fn with_tag_len(key: [u64; 4], tag_len: TagLen) -> Self {
+        HighwayMac { hasher: HighwayHash::new_key(key), tag_len }
+    }
+    
+    /// Tag length in bytes
+    pub fn tag_len(&self) -> usize {
+        self.tag_len.bytes()
+    }
+    
+    /// Authenticate the given data (may be called repeatedly)
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.write(data);
+    }
+    
+    /// Compute the tag, of length `tag_len()`
+    pub fn finalize(self) -> Vec<u8> {
+        let len = self.tag_len();
+        self.full_tag()[0..len].to_vec()
+    }
+    
+    /// Check the given tag, in constant time.
+    /// 
+    /// Returns false if `tag` does not have length `tag_len()`.
+    pub fn verify(self, tag: &[u8]) -> bool {
+        let len = self.tag_len();
+        let expected = self.full_tag();
+        ct_eq(&expected[0..len], tag)
+    }
+    
+    /// The 32-byte tag, of which shorter tags are a prefix
+    fn full_tag(self) -> [u8; 32] {
+        let hash = self.hasher.finalize_256();
+        let mut tag = [0u8; 32];
+        for (bytes, x) in tag.chunks_mut(8).zip(hash.iter()) {
+            bytes.copy_from_slice(&x.to_le_bytes());
+        }
+        tag
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    const KEY: [u64; 4] = [0x0706050403020100, 0x0F0E0D0C0B0A0908,
+            0x1716151413121110, 0x1F1E1D1C1B1A1918];
+    
+    fn mac(tag_len: TagLen, data: &[u8]) -> HighwayMac {
+        let mut mac = HighwayMac::with_tag_len(KEY, tag_len);
+        mac.update(data);
+        mac
+    }
+    
+    #[test]
+    fn tag() {
+        let data: Vec<u8> = (0..64).collect();
+        let hash = HighwayHash::hash_256(KEY, &data);
+        let tag = mac(TagLen::B32, &data).finalize();
+        assert_eq!(tag[0..8], hash[0].to_le_bytes());
+        assert_eq!(tag[24..32], hash[3].to_le_bytes());
+        
+        let mut mac = HighwayMac::new(KEY);
+        mac.update(&data[0..10]);
+        mac.update(&data[10..]);
+        assert_eq!(mac.finalize(), tag);
+    }
+    
+    #[test]
+    fn verify() {
+        let data = b"some message";
+        for &tag_len in &[TagLen::B8, TagLen::B16, TagLen::B32] {
+            let len = tag_len.bytes();
+            let tag = mac(tag_len, data).finalize();
+            assert_eq!(tag.len(), len);
+            assert!(mac(tag_len, data).verify(&tag));
+            
+            // wrong lengths
+            assert!(!mac(tag_len, data).verify(&tag[0..len - 1]));
+            if len < 32 {
+                let longer = mac(TagLen::B32, data).finalize();
+                assert_eq!(longer[0..len], tag[..]);
+                assert!(!mac(tag_len, data).verify(&longer[0..len + 1]));
+            }
+            
+            // tampered tag or message
+            for i in 0..len {
+                let mut bad = tag.clone();
+                bad[i] ^= 0x10;
+                assert!(!mac(tag_len, data).verify(&bad[0..len]));
+            }
+            assert!(!mac(tag_len, b"some messagf").verify(&tag[0..len]));
+        }
+    }
+    
+    #[test]
+    fn truncation_is_fixed() {
+        let tag = mac(TagLen::B32, b"abc").finalize();
+        assert!(!mac(TagLen::B32, b"abc").verify(&tag[0..8]));
+        assert!(!mac(TagLen::B16, b"abc").verify(&tag[0..8]));
+    }
+}
@@ -10,20 +10,75 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp::min;
+use std::hash::{Hasher, BuildHasher};
 use std::num::Wrapping as w;
 
+use k12::kangaroo_twelve;
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod sse41;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod avx2;
+
 #[allow(non_camel_case_types)]
 type w64 = w<u64>;
 
+/// Implementation of the packet update and final permutations.
+/// 
+/// All backends give identical results; the fastest supported by the CPU is
+/// selected at run-time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Backend {
+    Portable,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Sse41,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Avx2,
+}
+
+impl Backend {
+    fn detect() -> Backend {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Backend::Avx2;
+            }
+            if is_x86_feature_detected!("sse4.1") {
+                return Backend::Sse41;
+            }
+        }
+        Backend::Portable
+    }
+}
+
 
-/// Low-level API, use for implementing streams etc.
+/// Streaming hasher state.
+/// 
+/// Input may be split across any number of calls to `write`; bytes not
+/// filling a whole 32-byte packet are held back until more data arrives or
+/// the hash is finalized.
+#[derive(Clone)]
 pub struct HighwayHash {
     v0: [w64; 4],
     v1: [w64; 4],
     mul0: [w64; 4],
     mul1: [w64; 4],
+    buffer: [u8; 32],
+    buffered: usize,
+    backend: Backend,
 }
 
+// Version byte at the start of snapshots; bump on any change to the format
+const SNAPSHOT_VERSION: u8 = 1;
+
+// K12 customization string used by `HighwayHash::derive_key`
+const KEY_CUSTOMIZATION: &[u8] = b"HighwayHash key derivation";
+
+// some hard-coded random numbers
+const DEFAULT_KEY: [u64; 4] = [0x4ae1e91cf3b5737a, 0x4ea5ac492013cced,
+        0xb34430a80d547e23, 0xa77ddfe31c89436d];
+
 /// Copied from `arrayref` crate
 macro_rules! array_ref {
     ($arr:expr, $offset:expr, $len:expr) => {{
@@ -42,6 +97,9 @@ macro_rules! array_ref {
 }
 
 impl HighwayHash {
+    /// Length of the byte representation from `snapshot`
+    pub const SNAPSHOT_LEN: usize = 1 + 16 * 8 + 1 + 32;
+    
     /// Calculate the hash of the given data with 64-bit output.
     /// 
     /// Convenience function around `new`, `write` and `finalize_64`.
@@ -69,6 +127,46 @@ impl HighwayHash {
         hasher.finalize_256()
     }
     
+    /// Calculate 64-bit hashes of many independent messages.
+    /// 
+    /// Equivalent to `out[i] = hash_64(key, data[i])` for each `i`. Each
+    /// message's input is processed on its own; only finalization is
+    /// batched, interleaving the final permutations of four messages where
+    /// AVX2 is available, which is faster for short messages. Panics if
+    /// `data` and `out` differ in length.
+    pub fn hash_64_many(key: [u64; 4], data: &[&[u8]], out: &mut [u64]) {
+        HighwayHash::new_key(key).hash_64_many_from(data, out)
+    }
+    
+    /// Implementation of `hash_64_many`, starting from state `self`
+    fn hash_64_many_from(&self, data: &[&[u8]], out: &mut [u64]) {
+        assert_eq!(data.len(), out.len());
+        for (data, out) in data.chunks(4).zip(out.chunks_mut(4)) {
+            let mut hashers = [self.clone(), self.clone(), self.clone(), self.clone()];
+            for (hasher, data) in hashers.iter_mut().zip(data) {
+                hasher.write(data);
+                hasher.flush();
+            }
+            
+            // The final permutations dominate for short messages and are
+            // independent, so we interleave these. Only AVX2 has the
+            // registers to gain from this; with SSE4.1 it was slower than
+            // finalizing the messages one at a time.
+            match self.backend {
+                #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+                Backend::Avx2 => unsafe { avx2::final_permutes_x4(&mut hashers, 4) },
+                _ => {
+                    for hasher in hashers.iter_mut() {
+                        hasher.final_permutes(4);
+                    }
+                }
+            }
+            
+            for (out, hasher) in out.iter_mut().zip(hashers.iter()) {
+                *out = hasher.output_64();
+            }
+        }
+    }
     
     /// Creates a state with the given key
     pub fn new_key(key: [u64; 4]) -> Self {
@@ -91,35 +189,171 @@ impl HighwayHash {
                 mul1[2] ^ swap(key[2]),
                 mul1[3] ^ swap(key[3])];
         
-        HighwayHash { v0, v1, mul0, mul1 }
+        HighwayHash { v0, v1, mul0, mul1, buffer: [0; 32], buffered: 0,
+                backend: Backend::detect() }
+    }
+    
+    /// Creates a state with a key given as bytes.
+    /// 
+    /// The key is read as four little-endian `u64`, so the same bytes give
+    /// the same hasher on every platform.
+    pub fn from_le_bytes(key: [u8; 32]) -> Self {
+        HighwayHash::new_key(HighwayHash::key_from_le_bytes(key))
+    }
+    
+    /// Creates a state with a key derived from `secret`, of any length.
+    /// 
+    /// See `derive_key`.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        HighwayHash::new_key(HighwayHash::derive_key(secret))
+    }
+    
+    /// Read a key as four little-endian `u64`
+    pub fn key_from_le_bytes(key: [u8; 32]) -> [u64; 4] {
+        [u64::from_le_bytes(*array_ref!(key, 0, 8)),
+                u64::from_le_bytes(*array_ref!(key, 8, 8)),
+                u64::from_le_bytes(*array_ref!(key, 16, 8)),
+                u64::from_le_bytes(*array_ref!(key, 24, 8))]
+    }
+    
+    /// Derive a key from a secret of any length.
+    /// 
+    /// This is the 32-byte KangarooTwelve hash of `secret` with the
+    /// customization string `"HighwayHash key derivation"`, read with
+    /// `key_from_le_bytes`. Note that this does not stretch the secret; it
+    /// is not suitable for passwords.
+    pub fn derive_key(secret: &[u8]) -> [u64; 4] {
+        let hash = kangaroo_twelve(secret, KEY_CUSTOMIZATION, 32);
+        HighwayHash::key_from_le_bytes(*array_ref!(hash, 0, 32))
     }
     
     /// Creates a new state with a fixed key
     pub fn new() -> Self {
-        // some hard-coded random numbers
-        HighwayHash::new_key([0x4ae1e91cf3b5737a, 0x4ea5ac492013cced,
-                0xb34430a80d547e23, 0xa77ddfe31c89436d])
+        HighwayHash::new_key(DEFAULT_KEY)
+    }
+    
+    /// Serialize the current state, including any pending input.
+    /// 
+    /// The format is stable across platforms and versions of this crate:
+    /// a version byte (currently 1), then `v0`, `v1`, `mul0`, `mul1` as
+    /// little-endian `u64` lanes, then the number of pending bytes (0..31)
+    /// and a 32-byte buffer holding these (zero-padded).
+    /// 
+    /// Note that the snapshot includes key material.
+    pub fn snapshot(&self) -> [u8; HighwayHash::SNAPSHOT_LEN] {
+        let mut bytes = [0u8; HighwayHash::SNAPSHOT_LEN];
+        bytes[0] = SNAPSHOT_VERSION;
+        let mut i = 1;
+        for lanes in &[self.v0, self.v1, self.mul0, self.mul1] {
+            for lane in lanes {
+                bytes[i..i + 8].copy_from_slice(&lane.0.to_le_bytes());
+                i += 8;
+            }
+        }
+        bytes[i] = self.buffered as u8;
+        bytes[i + 1 .. i + 1 + self.buffered].copy_from_slice(&self.buffer[0..self.buffered]);
+        bytes
+    }
+    
+    /// Restore a state serialized with `snapshot`.
+    /// 
+    /// Returns `None` if the snapshot has an unknown version or is corrupt.
+    pub fn from_snapshot(bytes: &[u8; HighwayHash::SNAPSHOT_LEN]) -> Option<HighwayHash> {
+        if bytes[0] != SNAPSHOT_VERSION {
+            return None;
+        }
+        let mut lanes = [[w(0u64); 4]; 4];
+        let mut i = 1;
+        for lanes in lanes.iter_mut() {
+            for lane in lanes.iter_mut() {
+                *lane = w(u64::from_le_bytes(*array_ref!(bytes, i, 8)));
+                i += 8;
+            }
+        }
+        let buffered = bytes[i] as usize;
+        // padding past the buffered bytes is always written as zero
+        if buffered >= 32 || bytes[i + 1 + buffered..].iter().any(|&b| b != 0) {
+            return None;
+        }
+        let mut buffer = [0u8; 32];
+        buffer[0..buffered].copy_from_slice(&bytes[i + 1 .. i + 1 + buffered]);
+        
+        Some(HighwayHash {
+            v0: lanes[0], v1: lanes[1], mul0: lanes[2], mul1: lanes[3],
+            buffer, buffered,
+            backend: Backend::detect(),
+        })
     }
     
     /// Write the given data
-    pub fn write(&mut self, data: &[u8]) {
+    pub fn write(&mut self, mut data: &[u8]) {
+        if self.buffered != 0 {
+            // top up the pending packet first
+            let n = min(32 - self.buffered, data.len());
+            self.buffer[self.buffered..self.buffered + n].copy_from_slice(&data[0..n]);
+            self.buffered += n;
+            data = &data[n..];
+            if self.buffered < 32 {
+                return;
+            }
+            let packet = self.buffer;
+            self.update_packets(&packet);
+            self.buffered = 0;
+        }
+        
         let len = data.len();
         let excess = len % 32;
         let end = len - excess;
-        let mut i = 0;
-        while i < end {
-            self.update_packet(array_ref!(data, i, 32));
-            i += 32;
+        if end != 0 {
+            self.update_packets(&data[0..end]);
+        }
+        self.buffer[0..excess].copy_from_slice(&data[end..]);
+        self.buffered = excess;
+    }
+    
+    /// Write up to 16 bytes; fast path for the integer `write_*` methods.
+    #[inline]
+    fn write_small(&mut self, bytes: &[u8]) {
+        let pos = self.buffered;
+        if pos + bytes.len() < 32 {
+            self.buffer[pos..pos + bytes.len()].copy_from_slice(bytes);
+            self.buffered += bytes.len();
+        } else {
+            self.write(bytes);
         }
-        if excess != 0 {
-            self.update_remainder(&data[i..i+excess]);
+    }
+    
+    /// Process any pending bytes. Must be called exactly once, before the
+    /// final permutations.
+    fn flush(&mut self) {
+        if self.buffered != 0 {
+            let buffer = self.buffer;
+            self.update_remainder(&buffer[0..self.buffered]);
+            self.buffered = 0;
+        }
+    }
+    
+    /// Process whole packets; `data.len()` must be a multiple of 32
+    fn update_packets(&mut self, data: &[u8]) {
+        match self.backend {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => unsafe { avx2::update_packets(self, data) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse41 => unsafe { sse41::update_packets(self, data) },
+            Backend::Portable => {
+                let mut i = 0;
+                while i < data.len() {
+                    self.update_packet(array_ref!(data, i, 32));
+                    i += 32;
+                }
+            }
         }
     }
     
     /// Takes a packet of 32 bytes
-    pub fn update_packet(&mut self, packet: &[u8; 32]) {
+    fn update_packet(&mut self, packet: &[u8; 32]) {
         fn read_u64(bytes: &[u8; 8]) -> w64 {
-            w(unsafe{ *(bytes as *const [u8; 8] as *const u64) }.to_le())
+            w(u64::from_le_bytes(*bytes))
         }
         
         let lanes = [read_u64(array_ref!(packet, 0, 8)),
@@ -130,7 +364,7 @@ impl HighwayHash {
     }
     
     /// Adds the final 1..31 bytes, do not use if 0 remain
-    pub fn update_remainder(&mut self, bytes: &[u8]) {
+    fn update_remainder(&mut self, bytes: &[u8]) {
         fn rotate_32_by(count: usize, lanes: &mut [w64; 4]) {
             for i in 0..4 {
                 let half0 = lanes[i].0 as u32;
@@ -151,9 +385,10 @@ impl HighwayHash {
         }
         rotate_32_by(len, &mut self.v1);
         
-        // TODO: what is this logic? It's copied exactly from the C code; why?
-        // If lm4!=0 and len<16 then some bytes get missed. If lm4==0 and len>=16
-        // then some bytes get copied redundantly. Why not just memcpy anyway?
+        // This packing is copied exactly from the C code, which defines the
+        // algorithm; the known-answer tests depend on it. The last 1..3 bytes
+        // are spread over 3 positions; with len>=16 the last 4 bytes are
+        // copied again, even if already included.
         let mut packet = [0u8; 32];
         packet[0..l1].copy_from_slice(&bytes[0..l1]);
         if len & 16 != 0 {
@@ -166,7 +401,7 @@ impl HighwayHash {
             packet[16 + 2] = bytes[len - 1];
         }
         
-        self.update_packet(&packet);
+        self.update_packets(&packet);
     }
     /*
     fn update(&mut self, lanes: [w64; 4]) {
@@ -219,35 +454,77 @@ impl HighwayHash {
             self.mul1[i] ^= (self.v0[i] & w(0xFFFFFFFF)) * (self.v1[i] >> 32);
         }
         let (x, y) = zipper_merge_and_add(self.v1[1], self.v1[0]);
-        self.v0[1] = y; self.v0[0] = x;
+        self.v0[1] += y; self.v0[0] += x;
         let (x, y) = zipper_merge_and_add(self.v1[3], self.v1[2]);
-        self.v0[3] = y; self.v0[2] = x;
+        self.v0[3] += y; self.v0[2] += x;
         let (x, y) = zipper_merge_and_add(self.v0[1], self.v0[0]);
-        self.v1[1] = y; self.v1[0] = x;
+        self.v1[1] += y; self.v1[0] += x;
         let (x, y) = zipper_merge_and_add(self.v0[3], self.v0[2]);
-        self.v1[3] = y; self.v1[2] = x;
+        self.v1[3] += y; self.v1[2] += x;
     }
     
     
-    /// Rebind
-    pub fn finish(self) -> u64 { self.finalize_64() }
-    
     /// Compute the final hash value.
-    pub fn finalize_64(mut self) -> u64 {
-        self.final_permutes();
+    pub fn finalize_64(self) -> u64 {
+        self.finish_64(4)
+    }
+    
+    /// As `finalize_64`, with the given number of final permutation rounds
+    /// instead of 4.
+    /// 
+    /// For security-margin research only: results differ from HighwayHash.
+    #[cfg(feature = "research")]
+    pub fn finalize_64_rounds(self, rounds: usize) -> u64 {
+        self.finish_64(rounds)
+    }
+    
+    fn finish_64(mut self, rounds: usize) -> u64 {
+        self.flush();
+        self.final_permutes(rounds);
+        self.output_64()
+    }
+    
+    fn output_64(&self) -> u64 {
         (self.v0[0] + self.v1[0] + self.mul0[0] + self.mul1[0]).0
     }
     
     /// Compute the final hash value.
-    pub fn finalize_128(mut self) -> [u64; 2] {
-        self.final_permutes();
+    pub fn finalize_128(self) -> [u64; 2] {
+        self.finish_128(6)
+    }
+    
+    /// As `finalize_128`, with the given number of final permutation rounds
+    /// instead of 6.
+    /// 
+    /// For security-margin research only: results differ from HighwayHash.
+    #[cfg(feature = "research")]
+    pub fn finalize_128_rounds(self, rounds: usize) -> [u64; 2] {
+        self.finish_128(rounds)
+    }
+    
+    fn finish_128(mut self, rounds: usize) -> [u64; 2] {
+        self.flush();
+        self.final_permutes(rounds);
         let h0 = self.v0[0] + self.mul0[0] + self.v1[2] + self.mul1[2];
         let h1 = self.v0[1] + self.mul0[1] + self.v1[3] + self.mul1[3];
         [h0.0, h1.0]
     }
 
     /// Compute the final hash value.
-    pub fn finalize_256(mut self) -> [u64; 4] {
+    pub fn finalize_256(self) -> [u64; 4] {
+        self.finish_256(10)
+    }
+    
+    /// As `finalize_256`, with the given number of final permutation rounds
+    /// instead of 10.
+    /// 
+    /// For security-margin research only: results differ from HighwayHash.
+    #[cfg(feature = "research")]
+    pub fn finalize_256_rounds(self, rounds: usize) -> [u64; 4] {
+        self.finish_256(rounds)
+    }
+    
+    fn finish_256(mut self, rounds: usize) -> [u64; 4] {
         fn modular_reduction(a3_unmasked: w64, a2: w64, a1: w64, a0: w64)
                 -> (u64, u64)
         {
@@ -257,7 +534,8 @@ impl HighwayHash {
             (m0.0, m1.0)
         }
         
-        self.final_permutes();
+        self.flush();
+        self.final_permutes(rounds);
         let (h0, h1) = modular_reduction(self.v1[1] + self.mul1[1],
                 self.v1[0] + self.mul1[0],
                 self.v0[1] + self.mul0[1],
@@ -269,8 +547,18 @@ impl HighwayHash {
         [h0, h1, h2, h3]
     }
     
-    fn final_permutes(&mut self) {
-        for _ in 0..4 {
+    fn final_permutes(&mut self, rounds: usize) {
+        match self.backend {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => unsafe { avx2::final_permutes(self, rounds) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Sse41 => unsafe { sse41::final_permutes(self, rounds) },
+            Backend::Portable => self.final_permutes_portable(rounds),
+        }
+    }
+    
+    fn final_permutes_portable(&mut self, rounds: usize) {
+        for _ in 0..rounds {
             let v = self.v0;
             let permuted = [(v[2] >> 32) | (v[2] << 32),
                     (v[3] >> 32) | (v[3] << 32),
@@ -280,3 +568,585 @@ impl HighwayHash {
         }
     }
 }
+
+impl Default for HighwayHash {
+    fn default() -> Self {
+        HighwayHash::new()
+    }
+}
+
+/// Integers are written in little-endian byte order, hence results are
+/// consistent across platforms (excepting `usize`, `isize`).
+impl Hasher for HighwayHash {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        HighwayHash::write(self, bytes)
+    }
+    
+    #[inline]
+    fn write_u8(&mut self, x: u8) {
+        self.write_small(&[x])
+    }
+    
+    #[inline]
+    fn write_u16(&mut self, x: u16) {
+        self.write_small(&x.to_le_bytes())
+    }
+    
+    #[inline]
+    fn write_u32(&mut self, x: u32) {
+        self.write_small(&x.to_le_bytes())
+    }
+    
+    #[inline]
+    fn write_u64(&mut self, x: u64) {
+        self.write_small(&x.to_le_bytes())
+    }
+    
+    #[inline]
+    fn write_usize(&mut self, x: usize) {
+        self.write_small(&x.to_le_bytes())
+    }
+    
+    #[inline]
+    fn write_u128(&mut self, x: u128) {
+        self.write_small(&x.to_le_bytes())
+    }
+    
+    #[inline]
+    fn write_i128(&mut self, x: i128) {
+        self.write_small(&x.to_le_bytes())
+    }
+    
+    /// Calculate the 64-bit hash without consuming the state
+    fn finish(&self) -> u64 {
+        self.clone().finalize_64()
+    }
+}
+
+/// Builds `HighwayHash` instances with a fixed key, for use with `HashMap`
+/// and `HashSet`.
+#[derive(Clone, Debug)]
+pub struct HighwayBuildHasher {
+    key: [u64; 4],
+}
+
+impl HighwayBuildHasher {
+    /// Use the given key for all hashers
+    pub fn new(key: [u64; 4]) -> Self {
+        HighwayBuildHasher { key }
+    }
+}
+
+impl Default for HighwayBuildHasher {
+    /// Use the same fixed key as `HighwayHash::new`
+    fn default() -> Self {
+        HighwayBuildHasher::new(DEFAULT_KEY)
+    }
+}
+
+impl BuildHasher for HighwayBuildHasher {
+    type Hasher = HighwayHash;
+    
+    fn build_hasher(&self) -> HighwayHash {
+        HighwayHash::new_key(self.key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    
+    #[test]
+    fn split_writes() {
+        let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+        for &len in &[0, 1, 31, 32, 33, 64, 100, 200] {
+            let data = &data[0..len];
+            let expected = HighwayHash::hash_256([1, 2, 3, 4], data);
+            for split in 0..len + 1 {
+                let mut hasher = HighwayHash::new_key([1, 2, 3, 4]);
+                hasher.write(&data[0..split]);
+                hasher.write(&data[split..]);
+                assert_eq!(hasher.finalize_256(), expected);
+            }
+            
+            let mut hasher = HighwayHash::new_key([1, 2, 3, 4]);
+            for byte in data.chunks(1) {
+                hasher.write(byte);
+            }
+            assert_eq!(hasher.finalize_256(), expected);
+        }
+    }
+    
+    #[test]
+    fn hasher() {
+        let mut a = HighwayHash::new();
+        a.write_u8(1);
+        a.write_u32(0x05040302);
+        a.write_u64(0x0d0c0b0a09080706);
+        a.write_u16(0x0f0e);
+        let x = a.finish();
+        assert_eq!(a.finish(), x);
+        
+        let mut b = HighwayHash::new();
+        HighwayHash::write(&mut b, &(1..16).collect::<Vec<u8>>());
+        assert_eq!(b.finalize_64(), x);
+        
+        a.write_u64(0x1716151413121110);
+        a.write_u64(0x1f1e1d1c1b1a1918);
+        let mut b = HighwayHash::new();
+        HighwayHash::write(&mut b, &(1..32).collect::<Vec<u8>>());
+        assert_eq!(a.finish(), b.finalize_64());
+        
+        let mut a = HighwayHash::new();
+        a.write_u128(0x100f0e0d0c0b0a090807060504030201);
+        a.write_i128(-2);
+        let mut b = HighwayHash::new();
+        HighwayHash::write(&mut b, &(1..17).collect::<Vec<u8>>());
+        HighwayHash::write(&mut b, &[0xFE]);
+        HighwayHash::write(&mut b, &[0xFF; 15]);
+        assert_eq!(a.finish(), b.finalize_64());
+    }
+    
+    #[cfg(feature = "research")]
+    #[test]
+    fn finalize_rounds() {
+        let data: Vec<u8> = (0..100).collect();
+        let make = || {
+            let mut hasher = HighwayHash::new_key([1, 2, 3, 4]);
+            hasher.write(&data);
+            hasher
+        };
+        assert_eq!(make().finalize_64_rounds(4), make().finalize_64());
+        assert_eq!(make().finalize_128_rounds(6), make().finalize_128());
+        assert_eq!(make().finalize_256_rounds(10), make().finalize_256());
+        assert!(make().finalize_64_rounds(3) != make().finalize_64());
+        assert!(make().finalize_256_rounds(0) != make().finalize_256());
+    }
+    
+    fn backends() -> Vec<Backend> {
+        #[allow(unused_mut)]
+        let mut backends = vec![Backend::Portable];
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse4.1") {
+                backends.push(Backend::Sse41);
+            }
+            if is_x86_feature_detected!("avx2") {
+                backends.push(Backend::Avx2);
+            }
+        }
+        backends
+    }
+    
+    #[test]
+    fn backends_agree() {
+        let key = [0x0123456789abcdef, 0xfedcba9876543210, 1, 2];
+        let mut x = 0x9e3779b97f4a7c15u64;
+        let data: Vec<u8> = (0..300).map(|_| {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (x >> 56) as u8
+        }).collect();
+        
+        for &backend in &backends()[1..] {
+            for len in 0..data.len() + 1 {
+                let data = &data[0..len];
+                let make = |backend| {
+                    let mut hasher = HighwayHash::new_key(key);
+                    hasher.backend = backend;
+                    hasher.write(data);
+                    hasher
+                };
+                let (a, b) = (make(Backend::Portable), make(backend));
+                assert_eq!(a.v0, b.v0, "{:?}, length {}", backend, len);
+                assert_eq!(a.v1, b.v1, "{:?}, length {}", backend, len);
+                assert_eq!(a.mul0, b.mul0, "{:?}, length {}", backend, len);
+                assert_eq!(a.mul1, b.mul1, "{:?}, length {}", backend, len);
+                assert_eq!(a.clone().finalize_64(), b.clone().finalize_64());
+                assert_eq!(a.clone().finalize_128(), b.clone().finalize_128());
+                assert_eq!(a.finalize_256(), b.finalize_256());
+            }
+        }
+    }
+    
+    #[test]
+    fn hash_64_many() {
+        let data: Vec<u8> = (0..100).map(|i| (i * 7) as u8).collect();
+        let messages: Vec<&[u8]> = (0..75).map(|i| &data[i % 11 .. i % 11 + i]).collect();
+        let expected: Vec<u64> = messages.iter()
+                .map(|m| HighwayHash::hash_64(KEY, m))
+                .collect();
+        
+        for backend in backends() {
+            let mut init = HighwayHash::new_key(KEY);
+            init.backend = backend;
+            for n in 0..messages.len() + 1 {
+                let mut out = vec![0; n];
+                init.hash_64_many_from(&messages[0..n], &mut out);
+                assert_eq!(out, &expected[0..n], "{:?}, {} messages", backend, n);
+            }
+        }
+    }
+    
+    #[test]
+    fn fork() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut prefix = HighwayHash::new_key(KEY);
+        prefix.write(&data[0..37]);
+        let mut a = prefix.clone();
+        a.write(&data[37..50]);
+        prefix.write(&data[37..100]);
+        assert_eq!(a.finalize_128(), HighwayHash::hash_128(KEY, &data[0..50]));
+        assert_eq!(prefix.finalize_128(), HighwayHash::hash_128(KEY, &data[0..100]));
+    }
+    
+    #[test]
+    fn snapshot_resume() {
+        let data: Vec<u8> = (0..150).map(|i| (i * 13) as u8).collect();
+        for split in 0..data.len() + 1 {
+            let mut hasher = HighwayHash::new_key(KEY);
+            hasher.write(&data[0..split]);
+            let snapshot = hasher.snapshot();
+            
+            let mut hasher = HighwayHash::from_snapshot(&snapshot).unwrap();
+            hasher.write(&data[split..]);
+            assert_eq!(hasher.clone().finalize_64(), HighwayHash::hash_64(KEY, &data));
+            assert_eq!(hasher.clone().finalize_128(), HighwayHash::hash_128(KEY, &data));
+            assert_eq!(hasher.finalize_256(), HighwayHash::hash_256(KEY, &data));
+        }
+    }
+    
+    #[test]
+    fn snapshot_format() {
+        let mut hasher = HighwayHash::new_key(KEY);
+        hasher.write(&[0xAA; 35]);
+        let snapshot = hasher.snapshot();
+        assert_eq!(snapshot[0], 1);
+        assert_eq!(snapshot[129], 3);
+        assert_eq!(&snapshot[130..133], &[0xAA; 3]);
+        assert!(snapshot[133..].iter().all(|&b| b == 0));
+        
+        let mut bad = snapshot;
+        bad[0] = 2;
+        assert!(HighwayHash::from_snapshot(&bad).is_none());
+        let mut bad = snapshot;
+        bad[129] = 32;
+        assert!(HighwayHash::from_snapshot(&bad).is_none());
+        let mut bad = snapshot;
+        bad[133] = 1;
+        assert!(HighwayHash::from_snapshot(&bad).is_none());
+        let mut bad = snapshot;
+        bad[HighwayHash::SNAPSHOT_LEN - 1] = 1;
+        assert!(HighwayHash::from_snapshot(&bad).is_none());
+    }
+    
+    #[test]
+    fn key_from_bytes() {
+        let mut bytes = [0u8; 32];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        assert_eq!(HighwayHash::key_from_le_bytes(bytes), KEY);
+        assert_eq!(HighwayHash::from_le_bytes(bytes).finalize_64(), EXPECTED_64[0]);
+    }
+    
+    #[test]
+    fn derive_key() {
+        let key = HighwayHash::derive_key(b"secret");
+        assert_eq!(key, [0xe05eba24dd277561, 0x57f68ff883ef7d22,
+                0x88b1919bc25bc290, 0xd306846e9cd1133a]);
+        assert_ne!(HighwayHash::derive_key(b"secreu"), key);
+        assert_ne!(HighwayHash::derive_key(b""), key);
+        assert_eq!(HighwayHash::from_secret(b"secret").finalize_64(),
+                HighwayHash::new_key(key).finalize_64());
+    }
+    
+    #[test]
+    fn hash_map() {
+        let mut map = HashMap::with_hasher(HighwayBuildHasher::new([1, 2, 3, 4]));
+        for i in 0..100u64 {
+            map.insert(i, i * i);
+        }
+        for i in 0..100u64 {
+            assert_eq!(map.get(&i), Some(&(i * i)));
+        }
+        assert_eq!(map.get(&100), None);
+    }
+    
+    // Test vectors from highwayhash_test.cc in
+    // https://github.com/google/highwayhash: the message of length `i` is
+    // the bytes `0, 1, .., i-1`.
+    const KEY: [u64; 4] = [0x0706050403020100, 0x0F0E0D0C0B0A0908,
+            0x1716151413121110, 0x1F1E1D1C1B1A1918];
+    
+    const EXPECTED_64: [u64; 65] = [
+        0x907A56DE22C26E53, 0x7EAB43AAC7CDDD78, 0xB8D0569AB0B53D62,
+        0x5C6BEFAB8A463D80, 0xF205A46893007EDA, 0x2B8A1668E4A94541,
+        0xBD4CCC325BEFCA6F, 0x4D02AE1738F59482, 0xE1205108E55F3171,
+        0x32D2644EC77A1584, 0xF6E10ACDB103A90B, 0xC3BBF4615B415C15,
+        0x243CC2040063FA9C, 0xA89A58CE65E641FF, 0x24B031A348455A23,
+        0x40793F86A449F33B, 0xCFAB3489F97EB832, 0x19FE67D2C8C5C0E2,
+        0x04DD90A69C565CC2, 0x75D9518E2371C504, 0x38AD9B1141D3DD16,
+        0x0264432CCD8A70E0, 0xA9DB5A6288683390, 0xD7B05492003F028C,
+        0x205F615AEA59E51E, 0xEEE0C89621052884, 0x1BFC1A93A7284F4F,
+        0x512175B5B70DA91D, 0xF71F8976A0A2C639, 0xAE093FEF1F84E3E7,
+        0x22CA92B01161860F, 0x9FC7007CCF035A68, 0xA0C964D9ECD580FC,
+        0x2C90F73CA03181FC, 0x185CF84E5691EB9E, 0x4FC1F5EF2752AA9B,
+        0xF5B7391A5E0A33EB, 0xB9B84B83B4E96C9C, 0x5E42FE712A5CD9B4,
+        0xA150F2F90C3F97DC, 0x7FA522D75E2D637D, 0x181AD0CC0DFFD32B,
+        0x3889ED981E854028, 0xFB4297E8C586EE2D, 0x6D064A45BB28059C,
+        0x90563609B3EC860C, 0x7AA4FCE94097C666, 0x1326BAC06B911E08,
+        0xB926168D2B154F34, 0x9919848945B1948D, 0xA2A98FC534825EBE,
+        0xE9809095213EF0B6, 0x582E5483707BC0E9, 0x086E9414A88A6AF5,
+        0xEE86B98D20F6743D, 0xF89B7FF609B1C0A7, 0x4C7D9CC19E22C3E8,
+        0x9A97005024562A6F, 0x5DD41CF423E6EBEF, 0xDF13609C0468E227,
+        0x6E0DA4F64188155A, 0xB755BA4B50D7D4A1, 0x887A3484647479BD,
+        0xAB8EEBE9BF2139A0, 0x75542C5D4CD2A6FF,
+    ];
+    
+    const EXPECTED_128: [[u64; 2]; 65] = [
+        [0x0FED268F9D8FFEC7, 0x33565E767F093E6F],
+        [0xD6B0A8893681E7A8, 0xDC291DF9EB9CDCB4],
+        [0x3D15AD265A16DA04, 0x78085638DC32E868],
+        [0x0607621B295F0BEB, 0xBFE69A0FD9CEDD79],
+        [0x26399EB46DACE49E, 0x2E922AD039319208],
+        [0x3250BDC386D12ED8, 0x193810906C63C23A],
+        [0x6F476AB3CB896547, 0x7CDE576F37ED1019],
+        [0x2A401FCA697171B4, 0xBE1F03FF9F02796C],
+        [0xA1E96D84280552E8, 0x695CF1C63BEC0AC2],
+        [0x142A2102F31E63B2, 0x1A85B98C5B5000CC],
+        [0x51A1B70E26B6BC5B, 0x929E1F3B2DA45559],
+        [0x88990362059A415B, 0xBED21F22C47B7D13],
+        [0xCD1F1F5F1CAF9566, 0xA818BA8CE0F9C8D4],
+        [0xA225564112FE6157, 0xB2E94C78B8DDB848],
+        [0xBD492FEBD1CC0919, 0xCECD1DBC025641A2],
+        [0x142237A52BC4AF54, 0xE0796C0B6E26BCD7],
+        [0x414460FFD5A401AD, 0x029EA3D5019F18C8],
+        [0xC52A4B96C51C9962, 0xECB878B1169B5EA0],
+        [0xD940CA8F11FBEACE, 0xF93A46D616F8D531],
+        [0x8AC49D0AE5C0CBF5, 0x3FFDBF8DF51D7C93],
+        [0xAC6D279B852D00A8, 0x7DCD3A6BA5EBAA46],
+        [0xF11621BD93F08A56, 0x3173C398163DD9D5],
+        [0x0C4CE250F68CF89F, 0xB3123CDA411898ED],
+        [0x15AB97ED3D9A51CE, 0x7CE274479169080E],
+        [0xCD001E198D4845B8, 0xD0D9D98BD8AA2D77],
+        [0x34F3D617A0493D79, 0x7DD304F6397F7E16],
+        [0x5CB56890A9F4C6B6, 0x130829166567304F],
+        [0x30DA6F8B245BD1C0, 0x6F828B7E3FD9748C],
+        [0xE0580349204C12C0, 0x93F6DA0CAC5F441C],
+        [0xF648731BA5073045, 0x5FB897114FB65976],
+        [0x024F8354738A5206, 0x509A4918EB7E0991],
+        [0x06E7B465E8A57C29, 0x52415E3A07F5D446],
+        [0x1984DF66C1434AAA, 0x16FC1958F9B3E4B9],
+        [0x111678AFE0C6C36C, 0xF958B59DE5A2849D],
+        [0x773FBC8440FB0490, 0xC96ED5D243658536],
+        [0x91E3DC710BB6C941, 0xEA336A0BC1EEACE9],
+        [0x25CFE3815D7AD9D4, 0xF2E94F8C828FC59E],
+        [0xB9FB38B83CC288F2, 0x7479C4C8F850EC04],
+        [0x1D85D5C525982B8C, 0x6E26B1C16F48DBF4],
+        [0x8A4E55BD6060BDE7, 0x2134D599058B3FD0],
+        [0x2A958FF994778F36, 0xE8052D1AE61D6423],
+        [0x89233AE6BE453233, 0x3ACF9C87D7E8C0B9],
+        [0x4458F5E27EA9C8D5, 0x418FB49BCA2A5140],
+        [0x090301837ED12A68, 0x1017F69633C861E6],
+        [0x330DD84704D49590, 0x339DF1AD3A4BA6E4],
+        [0x569363A663F2C576, 0x363B3D95E3C95EF6],
+        [0xACC8D08586B90737, 0x2BA0E8087D4E28E9],
+        [0x39C27A27C86D9520, 0x8DB620A45160932E],
+        [0x8E6A4AEB671A072D, 0x6ED3561A10E47EE6],
+        [0x0011D765B1BEC74A, 0xD80E6E656EDE842E],
+        [0x2515D62B936AC64C, 0xCE088794D7088A7D],
+        [0x91621552C16E23AF, 0x264F0094EB23CCEF],
+        [0x1E21880D97263480, 0xD8654807D3A31086],
+        [0x39D76AAF097F432D, 0xA517E1E09D074739],
+        [0x0F17A4F337C65A14, 0x2F51215F69F976D4],
+        [0xA0FB5CDA12895E44, 0x568C3DC4D1F13CD1],
+        [0x93C8FC00D89C46CE, 0xBAD5DA947E330E69],
+        [0x817C07501D1A5694, 0x584D6EE72CBFAC2B],
+        [0x91D668AF73F053BF, 0xF98E647683C1E0ED],
+        [0x5281E1EF6B3CCF8B, 0xBC4CC3DF166083D8],
+        [0xAAD61B6DBEAAEEB9, 0xFF969D000C16787B],
+        [0x4325D84FC0475879, 0x14B919BD905F1C2D],
+        [0x79A176D1AA6BA6D1, 0xF1F720C5A53A2B86],
+        [0x74BD7018022F3EF0, 0x3AEA94A8AD5F4BCB],
+        [0x98BB1F7198D4C4F2, 0xE0BC0571DE918FC8],
+    ];
+    
+    const EXPECTED_256: [[u64; 4]; 65] = [
+        [0xDD44482AC2C874F5, 0xD946017313C7351F,
+                0xB3AEBECCB98714FF, 0x41DA233145751DF4],
+        [0xEDB941BCE45F8254, 0xE20D44EF3DCAC60F,
+                0x72651B9BCB324A47, 0x2073624CB275E484],
+        [0x3FDFF9DF24AFE454, 0x11C4BF1A1B0AE873,
+                0x115169CC6922597A, 0x1208F6590D33B42C],
+        [0x480AA0D70DD1D95C, 0x89225E7C6911D1D0,
+                0x8EA8426B8BBB865A, 0xE23DFBC390E1C722],
+        [0xC9CFC497212BE4DC, 0xA85F9DF6AFD2929B,
+                0x1FDA9F211DF4109E, 0x07E4277A374D4F9B],
+        [0xB4B4F566A4DC85B3, 0xBF4B63BA5E460142,
+                0x15F48E68CDDC1DE3, 0x0F74587D388085C6],
+        [0x6445C70A86ADB9B4, 0xA99CFB2784B4CEB6,
+                0xDAE29D40A0B2DB13, 0xB6526DF29A9D1170],
+        [0xD666B1A00987AD81, 0xA4F1F838EB8C6D37,
+                0xE9226E07D463E030, 0x5754D67D062C526C],
+        [0xF1B905B0ED768BC0, 0xE6976FF3FCFF3A45,
+                0x4FBE518DD9D09778, 0xD9A0AFEB371E0D33],
+        [0x80D8E4D70D3C2981, 0xF10FBBD16424F1A1,
+                0xCF5C2DBE9D3F0CD1, 0xC0BFE8F701B673F2],
+        [0xADE48C50E5A262BE, 0x8E9492B1FDFE38E0,
+                0x0784B74B2FE9B838, 0x0E41D574DB656DCD],
+        [0xA1BE77B9531807CF, 0xBA97A7DE6A1A9738,
+                0xAF274CEF9C8E261F, 0x3E39B935C74CE8E8],
+        [0x15AD3802E3405857, 0x9D11CBDC39E853A0,
+                0x23EA3E993C31B225, 0x6CD9E9E3CAF4212E],
+        [0x01C96F5EB1D77C36, 0xA367F9C1531F95A6,
+                0x1F94A3427CDADCB8, 0x97F1000ABF3BD5D3],
+        [0x0815E91EEEFF8E41, 0x0E0C28FA6E21DF5D,
+                0x4EAD8E62ED095374, 0x3FFD01DA1C9D73E6],
+        [0xC11905707842602E, 0x62C3DB018501B146,
+                0x85F5AD17FA3406C1, 0xC884F87BD4FEC347],
+        [0xF51AD989A1B6CD1F, 0xF7F075D62A627BD9,
+                0x7E01D5F579F28A06, 0x1AD415C16A174D9F],
+        [0x19F4CFA82CA4068E, 0x3B9D4ABD3A9275B9,
+                0x8000B0DDE9C010C6, 0x8884D50949215613],
+        [0x126D6C7F81AB9F5D, 0x4EDAA3C5097716EE,
+                0xAF121573A7DD3E49, 0x9001AC85AA80C32D],
+        [0x06AABEF9149155FA, 0xDF864F4144E71C3D,
+                0xFDBABCE860BC64DA, 0xDE2BA54792491CB6],
+        [0xADFC6B4035079FDB, 0xA087B7328E486E65,
+                0x46D1A9935A4623EA, 0xE3895C440D3CEE44],
+        [0xB5F9D31DEEA3B3DF, 0x8F3024E20A06E133,
+                0xF24C38C8288FE120, 0x703F1DCF9BD69749],
+        [0x2B3C0B854794EFE3, 0x1C5D3F969BDACEA0,
+                0x81F16AAFA563AC2E, 0x23441C5A79D03075],
+        [0x418AF8C793FD3762, 0xBC6B8E9461D7F924,
+                0x776FF26A2A1A9E78, 0x3AA0B7BFD417CA6E],
+        [0xCD03EA2AD255A3C1, 0x0185FEE5B59C1B2A,
+                0xD1F438D44F9773E4, 0xBE69DD67F83B76E4],
+        [0xF951A8873887A0FB, 0x2C7B31D2A548E0AE,
+                0x44803838B6186EFA, 0xA3C78EC7BE219F72],
+        [0x958FF151EA0D8C08, 0x4B7E8997B4F63488,
+                0xC78E074351C5386D, 0xD95577556F20EEFA],
+        [0x29A917807FB05406, 0x3318F884351F578C,
+                0xDD24EA6EF6F6A7FA, 0xE74393465E97AEFF],
+        [0x98240880935E6CCB, 0x1FD0D271B09F97DA,
+                0x56E786472700B183, 0x291649F99F747817],
+        [0x1BD4954F7054C556, 0xFFDB2EFF7C596CEB,
+                0x7C6AC69A1BAB6B5B, 0x0F037670537FC153],
+        [0x8825E38897597498, 0x647CF6EBAF6332C1,
+                0x552BD903DC28C917, 0x72D7632C00BFC5AB],
+        [0x6880E276601A644D, 0xB3728B20B10FB7DA,
+                0xD0BD12060610D16E, 0x8AEF14EF33452EF2],
+        [0xBCE38C9039A1C3FE, 0x42D56326A3C11289,
+                0xE35595F764FCAEA9, 0xC9B03C6BC9475A99],
+        [0xF60115CBF034A6E5, 0x6C36EA75BFCE46D0,
+                0x3B17C8D382725990, 0x7EDAA2ED11007A35],
+        [0x1326E959EDF9DEA2, 0xC4776801739F720C,
+                0x5169500FD762F62F, 0x8A0DD0D90A2529AB],
+        [0x935149D503D442D4, 0xFF6BB41302DAD144,
+                0x339CB012CD9D36EC, 0xE61D53619ECC2230],
+        [0x528BC888AA50B696, 0xB8AEECA36084E1FC,
+                0xA158151EC0243476, 0x02C14AAD097CEC44],
+        [0xBED688A72217C327, 0x1EE65114F760873F,
+                0x3F5C26B37D3002A6, 0xDDF2E895631597B9],
+        [0xE7DB21CF2B0B51AD, 0xFAFC6324F4B0AB6C,
+                0xB0857244C22D9C5B, 0xF0AD888D1E05849C],
+        [0x05519793CD4DCB00, 0x3C594A3163067DEB,
+                0xAC75081ACF119E34, 0x5AC86297805CB094],
+        [0x09228D8C22B5779E, 0x19644DB2516B7E84,
+                0x2B92C8ABF83141A0, 0x7F785AD725E19391],
+        [0x59C42E5D46D0A74B, 0x5EA53C65CA036064,
+                0x48A9916BB635AEB4, 0xBAE6DF143F54E9D4],
+        [0x5EB623696D03D0E3, 0xD53D78BCB41DA092,
+                0xFE2348DC52F6B10D, 0x64802457632C8C11],
+        [0x43B61BB2C4B85481, 0xC6318C25717E80A1,
+                0x8C4A7F4D6F9C687D, 0xBD0217E035401D7C],
+        [0x7F51CA5743824C37, 0xB04C4D5EB11D703A,
+                0x4D511E1ECBF6F369, 0xD66775EA215456E2],
+        [0x39B409EEF87E45CC, 0x52B8E8C459FC79B3,
+                0x44920918D1858C24, 0x80F07B645EEE0149],
+        [0xCE8694D1BE9AD514, 0xBFA19026526836E7,
+                0x1EA4FDF6E4902A7D, 0x380C4458D696E1FE],
+        [0xD189E18BF823A0A4, 0x1F3B353BE501A7D7,
+                0xA24F77B4E02E2884, 0x7E94646F74F9180C],
+        [0xAFF8C635D325EC48, 0x2C2E0AA414038D0B,
+                0x4ED37F611A447467, 0x39EC38E33B501489],
+        [0x2A2BFDAD5F83F197, 0x013D3E6EBEF274CC,
+                0xE1563C0477726155, 0xF15A8A5DE932037E],
+        [0xD5D1F91EC8126332, 0x10110B9BF9B1FF11,
+                0xA175AB26541C6032, 0x87BADC5728701552],
+        [0xC7B5A92CD8082884, 0xDDA62AB61B2EEEFB,
+                0x8F9882ECFEAE732F, 0x6B38BD5CC01F4FFB],
+        [0xCF6EF275733D32F0, 0xA3F0822DA2BF7D8B,
+                0x304E7435F512406A, 0x0B28E3EFEBB3172D],
+        [0xE698F80701B2E9DB, 0x66AE2A819A8A8828,
+                0x14EA9024C9B8F2C9, 0xA7416170523EB5A4],
+        [0x3A917E87E307EDB7, 0x17B4DEDAE34452C1,
+                0xF689F162E711CC70, 0x29CE6BFE789CDD0E],
+        [0x0EFF3AD8CB155D8E, 0x47CD9EAD4C0844A2,
+                0x46C8E40EE6FE21EB, 0xDEF3C25DF0340A51],
+        [0x03FD86E62B82D04D, 0x32AB0D600717136D,
+                0x682B0E832B857A89, 0x138CE3F1443739B1],
+        [0x2F77C754C4D7F902, 0x1053E0A9D9ADBFEA,
+                0x58E66368544AE70A, 0xC48A829C72DD83CA],
+        [0xF900EB19E466A09F, 0x31BE9E01A8C7D314,
+                0x3AFEC6B8CA08F471, 0xB8C0EB0F87FFE7FB],
+        [0xDB277D8FBE3C8EFB, 0x53CE6877E11AA57B,
+                0x719C94D20D9A7E7D, 0xB345B56392453CC9],
+        [0x37639C3BDBA4F2C9, 0x6095E7B336466DC8,
+                0x3A8049791E65B88A, 0x82C988CDE5927CD5],
+        [0x6B1FB1A714234AE4, 0x20562E255BA6467E,
+                0x3E2B892D40F3D675, 0xF40CE3FBE41ED768],
+        [0x8EE11CB1B287C92A, 0x8FC2AAEFF63D266D,
+                0x66643487E6EB9F03, 0x578AA91DE8D56873],
+        [0xF5B1F8266A3AEB67, 0x83B040BE4DEC1ADD,
+                0x7FE1C8635B26FBAE, 0xF4A3A447DEFED79F],
+        [0x90D8E6FF6AC12475, 0x1A422A196EDAC1F2,
+                0x9E3765FE1F8EB002, 0xC1BDD7C4C351CFBE],
+    ];
+    
+    fn kat_data() -> Vec<u8> {
+        (0..64).collect()
+    }
+    
+    fn kat_hasher(backend: Backend, data: &[u8]) -> HighwayHash {
+        let mut hasher = HighwayHash::new_key(KEY);
+        hasher.backend = backend;
+        hasher.write(data);
+        hasher
+    }
+    
+    #[test]
+    fn known_answers_64() {
+        let data = kat_data();
+        for backend in backends() {
+            for i in 0..65 {
+                assert_eq!(kat_hasher(backend, &data[0..i]).finalize_64(),
+                        EXPECTED_64[i], "{:?}, length {}", backend, i);
+            }
+        }
+    }
+    
+    #[test]
+    fn known_answers_128() {
+        let data = kat_data();
+        for backend in backends() {
+            for i in 0..65 {
+                assert_eq!(kat_hasher(backend, &data[0..i]).finalize_128(),
+                        EXPECTED_128[i], "{:?}, length {}", backend, i);
+            }
+        }
+    }
+    
+    #[test]
+    fn known_answers_256() {
+        let data = kat_data();
+        for backend in backends() {
+            for i in 0..65 {
+                assert_eq!(kat_hasher(backend, &data[0..i]).finalize_256(),
+                        EXPECTED_256[i], "{:?}, length {}", backend, i);
+            }
+        }
+    }
+}
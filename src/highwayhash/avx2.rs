@@ -0,0 +1,123 @@
+// Copyright 2017 Diggory Hardy and original developers:
+// https://github.com/google/highwayhash
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0>
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! AVX2 backend: each of `v0`, `v1`, `mul0`, `mul1` fits one 256-bit register.
+//!
+//! Based on `hh_avx2.h` from the reference implementation.
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::HighwayHash;
+
+struct State {
+    v0: __m256i,
+    v1: __m256i,
+    mul0: __m256i,
+    mul1: __m256i,
+}
+
+impl State {
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn load(hasher: &HighwayHash) -> State {
+        State {
+            v0: _mm256_loadu_si256(hasher.v0.as_ptr() as *const __m256i),
+            v1: _mm256_loadu_si256(hasher.v1.as_ptr() as *const __m256i),
+            mul0: _mm256_loadu_si256(hasher.mul0.as_ptr() as *const __m256i),
+            mul1: _mm256_loadu_si256(hasher.mul1.as_ptr() as *const __m256i),
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn store(&self, hasher: &mut HighwayHash) {
+        _mm256_storeu_si256(hasher.v0.as_mut_ptr() as *mut __m256i, self.v0);
+        _mm256_storeu_si256(hasher.v1.as_mut_ptr() as *mut __m256i, self.v1);
+        _mm256_storeu_si256(hasher.mul0.as_mut_ptr() as *mut __m256i, self.mul0);
+        _mm256_storeu_si256(hasher.mul1.as_mut_ptr() as *mut __m256i, self.mul1);
+    }
+
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn update(&mut self, lanes: __m256i) {
+        self.v1 = _mm256_add_epi64(self.v1, _mm256_add_epi64(self.mul0, lanes));
+        self.mul0 = _mm256_xor_si256(self.mul0,
+                _mm256_mul_epu32(self.v1, _mm256_srli_epi64(self.v0, 32)));
+        self.v0 = _mm256_add_epi64(self.v0, self.mul1);
+        self.mul1 = _mm256_xor_si256(self.mul1,
+                _mm256_mul_epu32(self.v0, _mm256_srli_epi64(self.v1, 32)));
+        self.v0 = _mm256_add_epi64(self.v0, zipper_merge(self.v1));
+        self.v1 = _mm256_add_epi64(self.v1, zipper_merge(self.v0));
+    }
+}
+
+/// Same byte shuffle as the portable `zipper_merge_and_add`, within each
+/// 128-bit half.
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn zipper_merge(v: __m256i) -> __m256i {
+    let mask = _mm256_set_epi64x(0x070806090D0A040B, 0x000F010E05020C03,
+            0x070806090D0A040B, 0x000F010E05020C03);
+    _mm256_shuffle_epi8(v, mask)
+}
+
+/// Swap 128-bit halves and the 32-bit halves of each lane.
+#[target_feature(enable = "avx2")]
+#[inline]
+unsafe fn permute(v: __m256i) -> __m256i {
+    let v = _mm256_permute4x64_epi64(v, 0b01_00_11_10);
+    _mm256_shuffle_epi32(v, 0b10_11_00_01)
+}
+
+/// Process whole packets; `data.len()` must be a multiple of 32.
+#[target_feature(enable = "avx2")]
+pub unsafe fn update_packets(hasher: &mut HighwayHash, data: &[u8]) {
+    debug_assert_eq!(data.len() % 32, 0);
+    let mut state = State::load(hasher);
+    for packet in data.chunks(32) {
+        state.update(_mm256_loadu_si256(packet.as_ptr() as *const __m256i));
+    }
+    state.store(hasher);
+}
+
+#[target_feature(enable = "avx2")]
+pub unsafe fn final_permutes(hasher: &mut HighwayHash, rounds: usize) {
+    let mut state = State::load(hasher);
+    for _ in 0..rounds {
+        let permuted = permute(state.v0);
+        state.update(permuted);
+    }
+    state.store(hasher);
+}
+
+/// As `final_permutes`, but with four independent states interleaved.
+#[target_feature(enable = "avx2")]
+pub unsafe fn final_permutes_x4(hashers: &mut [HighwayHash; 4], rounds: usize) {
+    let mut a = State::load(&hashers[0]);
+    let mut b = State::load(&hashers[1]);
+    let mut c = State::load(&hashers[2]);
+    let mut d = State::load(&hashers[3]);
+    for _ in 0..rounds {
+        let (pa, pb, pc, pd) = (permute(a.v0), permute(b.v0), permute(c.v0), permute(d.v0));
+        a.update(pa);
+        b.update(pb);
+        c.update(pc);
+        d.update(pd);
+    }
+    a.store(&mut hashers[0]);
+    b.store(&mut hashers[1]);
+    c.store(&mut hashers[2]);
+    d.store(&mut hashers[3]);
+}
@@ -0,0 +1,127 @@
+// Copyright 2017 Diggory Hardy and original developers:
+// https://github.com/google/highwayhash
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0>
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SSE4.1 backend: each of `v0`, `v1`, `mul0`, `mul1` is held as two 128-bit
+//! halves, lanes 0-1 (`l`) and 2-3 (`h`).
+//!
+//! Based on `hh_sse41.h` from the reference implementation.
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::HighwayHash;
+
+struct State {
+    v0l: __m128i,
+    v0h: __m128i,
+    v1l: __m128i,
+    v1h: __m128i,
+    mul0l: __m128i,
+    mul0h: __m128i,
+    mul1l: __m128i,
+    mul1h: __m128i,
+}
+
+#[target_feature(enable = "sse4.1")]
+#[inline]
+unsafe fn load(lanes: *const u64) -> (__m128i, __m128i) {
+    (_mm_loadu_si128(lanes as *const __m128i),
+            _mm_loadu_si128(lanes.offset(2) as *const __m128i))
+}
+
+#[target_feature(enable = "sse4.1")]
+#[inline]
+unsafe fn store(lanes: *mut u64, l: __m128i, h: __m128i) {
+    _mm_storeu_si128(lanes as *mut __m128i, l);
+    _mm_storeu_si128(lanes.offset(2) as *mut __m128i, h);
+}
+
+impl State {
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn load(hasher: &HighwayHash) -> State {
+        let (v0l, v0h) = load(hasher.v0.as_ptr() as *const u64);
+        let (v1l, v1h) = load(hasher.v1.as_ptr() as *const u64);
+        let (mul0l, mul0h) = load(hasher.mul0.as_ptr() as *const u64);
+        let (mul1l, mul1h) = load(hasher.mul1.as_ptr() as *const u64);
+        State { v0l, v0h, v1l, v1h, mul0l, mul0h, mul1l, mul1h }
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn store(&self, hasher: &mut HighwayHash) {
+        store(hasher.v0.as_mut_ptr() as *mut u64, self.v0l, self.v0h);
+        store(hasher.v1.as_mut_ptr() as *mut u64, self.v1l, self.v1h);
+        store(hasher.mul0.as_mut_ptr() as *mut u64, self.mul0l, self.mul0h);
+        store(hasher.mul1.as_mut_ptr() as *mut u64, self.mul1l, self.mul1h);
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    #[inline]
+    unsafe fn update(&mut self, lanes_l: __m128i, lanes_h: __m128i) {
+        self.v1l = _mm_add_epi64(self.v1l, _mm_add_epi64(self.mul0l, lanes_l));
+        self.v1h = _mm_add_epi64(self.v1h, _mm_add_epi64(self.mul0h, lanes_h));
+        self.mul0l = _mm_xor_si128(self.mul0l,
+                _mm_mul_epu32(self.v1l, _mm_srli_epi64(self.v0l, 32)));
+        self.mul0h = _mm_xor_si128(self.mul0h,
+                _mm_mul_epu32(self.v1h, _mm_srli_epi64(self.v0h, 32)));
+        self.v0l = _mm_add_epi64(self.v0l, self.mul1l);
+        self.v0h = _mm_add_epi64(self.v0h, self.mul1h);
+        self.mul1l = _mm_xor_si128(self.mul1l,
+                _mm_mul_epu32(self.v0l, _mm_srli_epi64(self.v1l, 32)));
+        self.mul1h = _mm_xor_si128(self.mul1h,
+                _mm_mul_epu32(self.v0h, _mm_srli_epi64(self.v1h, 32)));
+        self.v0l = _mm_add_epi64(self.v0l, zipper_merge(self.v1l));
+        self.v0h = _mm_add_epi64(self.v0h, zipper_merge(self.v1h));
+        self.v1l = _mm_add_epi64(self.v1l, zipper_merge(self.v0l));
+        self.v1h = _mm_add_epi64(self.v1h, zipper_merge(self.v0h));
+    }
+}
+
+/// Same byte shuffle as the portable `zipper_merge_and_add`.
+#[target_feature(enable = "sse4.1")]
+#[inline]
+unsafe fn zipper_merge(v: __m128i) -> __m128i {
+    let mask = _mm_set_epi64x(0x070806090D0A040B, 0x000F010E05020C03);
+    _mm_shuffle_epi8(v, mask)
+}
+
+/// Swap the 32-bit halves of each lane.
+#[target_feature(enable = "sse4.1")]
+#[inline]
+unsafe fn rotate_32(v: __m128i) -> __m128i {
+    _mm_shuffle_epi32(v, 0b10_11_00_01)
+}
+
+/// Process whole packets; `data.len()` must be a multiple of 32.
+#[target_feature(enable = "sse4.1")]
+pub unsafe fn update_packets(hasher: &mut HighwayHash, data: &[u8]) {
+    debug_assert_eq!(data.len() % 32, 0);
+    let mut state = State::load(hasher);
+    for packet in data.chunks(32) {
+        let p = packet.as_ptr() as *const __m128i;
+        state.update(_mm_loadu_si128(p), _mm_loadu_si128(p.offset(1)));
+    }
+    state.store(hasher);
+}
+
+#[target_feature(enable = "sse4.1")]
+pub unsafe fn final_permutes(hasher: &mut HighwayHash, rounds: usize) {
+    let mut state = State::load(hasher);
+    for _ in 0..rounds {
+        let (l, h) = (rotate_32(state.v0h), rotate_32(state.v0l));
+        state.update(l, h);
+    }
+    state.store(hasher);
+}
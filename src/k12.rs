@@ -7,7 +7,11 @@
 // and related or neighboring rights to the source code in this file.
 // http://creativecommons.org/publicdomain/zero/1.0/
 
-use std::cmp::min;
+use std::cmp::{min, max};
+use std::thread;
+
+use keccak_p::select_keccak_p1600_x4;
+use sponge::{Sponge, SpongeReader};
 
 #[macro_use]
 mod macros {
@@ -27,265 +31,455 @@ mod macros {
             }
         }}
     }
+}
 
-    macro_rules! REPEAT4 {
-        ($e: expr) => ( $e; $e; $e; $e; )
-    }
+fn read_u64(bytes: &[u8; 8]) -> u64 {
+    u64::from_le_bytes(*bytes)
+}
+fn write_u64(val: u64) -> [u8; 8] {
+    val.to_le_bytes()
+}
 
-    macro_rules! REPEAT5 {
-        ($e: expr) => ( $e; $e; $e; $e; $e; )
-    }
+/// Number of rounds of Keccak-p used by K12 and TurboSHAKE
+const ROUNDS: usize = 12;
+
+/// Rate of the sponge in bytes (K12 and TurboSHAKE128)
+const RATE: usize = 1344 / 8;
+
+/// Rate of TurboSHAKE256 and M14 in bytes
+const RATE_256: usize = 1088 / 8;
+
+/// Chunk size of the tree mode, in bytes
+const CHUNK: usize = 8192;
+
+/// Length of chaining values in bytes (KT128)
+const CV_LEN: usize = 256 / 8;
+
+/// Length of chaining values in bytes (KT256 and M14)
+const CV_LEN_256: usize = 512 / 8;
+
+/// A chaining value; only the first `cv_len` bytes are used
+type Cv = [u8; CV_LEN_256];
 
-    macro_rules! REPEAT6 {
-        ($e: expr) => ( $e; $e; $e; $e; $e; $e; )
+/// Parameters of the tree mode
+#[derive(Clone, Copy)]
+struct Params {
+    // rounds of Keccak-p
+    rounds: usize,
+    // rate of the sponge in bytes
+    rate: usize,
+    // length of chaining values in bytes
+    cv_len: usize,
+}
+
+const KT128: Params = Params { rounds: ROUNDS, rate: RATE, cv_len: CV_LEN };
+const KT256: Params = Params { rounds: ROUNDS, rate: RATE_256, cv_len: CV_LEN_256 };
+const M14: Params = Params { rounds: 14, rate: RATE_256, cv_len: CV_LEN_256 };
+
+/// Encode `x` within `buf`, returning the used part
+pub fn right_encode(x: usize, buf: &mut [u8; 9]) -> &[u8] {
+    let mut x = x as u64;
+    let mut len = 0;
+    while x > 0 {
+        buf[7 - len] = x as u8;
+        x >>= 8;
+        len += 1;
     }
+    buf[8] = len as u8;
+    &buf[8 - len..]
+}
+
+/// Chaining value of a complete leaf
+fn leaf_cv(chunk: &[u8], params: Params) -> Cv {
+    let mut sponge = Sponge::new(params.rate, params.rounds);
+    sponge.absorb(chunk);
+    sponge.pad(0x0B);
+    let mut cv = [0u8; CV_LEN_256];
+    sponge.squeeze(&mut cv[0..params.cv_len]);
+    cv
+}
 
-    macro_rules! REPEAT24 {
-        ($e: expr, $s: expr) => (
-            REPEAT6!({ $e; $s; });
-            REPEAT6!({ $e; $s; });
-            REPEAT6!({ $e; $s; });
-            REPEAT5!({ $e; $s; });
-            $e;
-        )
+/// Chaining values of four complete leaves, using the interleaved permutation
+fn leaf_cvs_x4(chunks: &[u8], params: Params) -> [Cv; 4] {
+    debug_assert_eq!(chunks.len(), 4 * CHUNK);
+    let mut lanes = [[0u64; 4]; 25];
+    let absorb = |lanes: &mut [[u64; 4]; 25], offset: usize, n_lanes: usize| {
+        for k in 0..4 {
+            let block = &chunks[k * CHUNK + offset ..];
+            for (i, lane) in lanes[0..n_lanes].iter_mut().enumerate() {
+                lane[k] ^= read_u64(array_ref!(block, 8 * i, 8));
+            }
+        }
+    };
+    
+    let permute = select_keccak_p1600_x4();
+    let rate = params.rate;
+    let mut offset = 0;
+    while offset + rate <= CHUNK {
+        absorb(&mut lanes, offset, rate / 8);
+        permute(&mut lanes, params.rounds);
+        offset += rate;
+    }
+    // CHUNK and the rate are both multiples of 8, so the remainder is whole lanes
+    let rem = CHUNK - offset;
+    absorb(&mut lanes, offset, rem / 8);
+    for lane in lanes[rem / 8].iter_mut() {
+        *lane ^= 0x0B;
     }
+    for lane in lanes[rate / 8 - 1].iter_mut() {
+        *lane ^= 0x80 << 56;
+    }
+    permute(&mut lanes, params.rounds);
+    
+    let mut cvs = [[0u8; CV_LEN_256]; 4];
+    for k in 0..4 {
+        for i in 0..params.cv_len / 8 {
+            cvs[k][8 * i .. 8 * i + 8].copy_from_slice(&write_u64(lanes[i][k]));
+        }
+    }
+    cvs
+}
 
-    macro_rules! FOR5 {
-        ($v: expr, $s: expr, $e: expr) => {
-            $v = 0;
-            REPEAT4!({
-                $e;
-                $v += $s;
-            });
-            $e;
+/// Compute chaining values of the whole leaves in `input`, four at a time
+/// where possible.
+fn leaf_cvs(input: &[u8], cvs: &mut [Cv], params: Params) {
+    let mut groups = input.chunks(4 * CHUNK).zip(cvs.chunks_mut(4));
+    for (group, cvs) in &mut groups {
+        if group.len() == 4 * CHUNK {
+            cvs.copy_from_slice(&leaf_cvs_x4(group, params));
+        } else {
+            for (chunk, cv) in group.chunks(CHUNK).zip(cvs.iter_mut()) {
+                *cv = leaf_cv(chunk, params);
+            }
         }
     }
 }
 
-mod lanes {
-    pub const RC: [u64; 12] = [
-        0x000000008000808b,
-        0x800000000000008b,
-        0x8000000000008089,
-        0x8000000000008003,
-        0x8000000000008002,
-        0x8000000000000080,
-        0x000000000000800a,
-        0x800000008000000a,
-        0x8000000080008081,
-        0x8000000000008080,
-        0x0000000080000001,
-        0x8000000080008008,
-    ];
-
-    // (0..24).map(|t| ((t+1)*(t+2)/2) % 64)
-    pub const RHO: [u32; 24] = [
-        1, 3, 6, 10, 15, 21,28, 36, 45, 55, 2, 14, 27,
-        41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44
-    ];
-    pub const PI: [usize; 24] = [
-        10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23,
-        19, 13, 12, 2, 20, 14, 22, 9, 6, 1
-    ];
-
-    pub fn keccak(lanes: &mut [u64; 25]) {
-        let mut c = [0u64; 5];
-        let (mut x, mut y): (usize, usize);
-        
-        for round in 0..12 {
-            // θ
-            FOR5!(x, 1, {
-                c[x] = lanes[x] ^ lanes[x+5] ^ lanes[x+10] ^ lanes[x+15] ^ lanes[x+20];
-            });
-            
-            FOR5!(x, 1, {
-                FOR5!(y, 5, {
-                    lanes[x + y] ^= c[(x+4)%5] ^ c[(x+1)%5].rotate_left(1);
-                });
-            });
-            
-            // ρ and π
-            let mut a = lanes[1];
-            x = 0;
-            REPEAT24!({
-                c[0] = lanes[PI[x]];
-                lanes[PI[x]] = a.rotate_left(RHO[x]);
-            }, {
-                a = c[0];
-                x += 1;
-            });
+/// Streaming KangarooTwelve hasher.
+/// 
+/// Input is processed in chunks of 8192 bytes as it arrives; memory use is
+/// constant regardless of the message length.
+/// 
+/// Optionally, leaves may be processed by multiple threads (see
+/// `with_threads`); the output is identical.
+#[derive(Clone)]
+pub struct KangarooTwelve {
+    // final node; absorbs the first chunk then the chaining values
+    final_node: Sponge,
+    // current leaf (chunk after the first)
+    leaf: Sponge,
+    // bytes in the current chunk
+    chunk_len: usize,
+    // number of leaves started, including the current one
+    leaves: usize,
+    // number of threads used to process leaves
+    threads: usize,
+    params: Params,
+}
+
+impl KangarooTwelve {
+    /// Create a new hasher
+    pub fn new() -> Self {
+        KangarooTwelve::with_threads(1)
+    }
+    
+    /// Create a new hasher using up to `threads` threads.
+    /// 
+    /// Where a single call to `update` includes at least `4 * threads`
+    /// complete leaves (8 KiB chunks after the first), their chaining values
+    /// are computed in parallel; below that, starting the threads would cost
+    /// about as much as the hashing. With `threads <= 1` all work happens on
+    /// the calling thread.
+    pub fn with_threads(threads: usize) -> Self {
+        KangarooTwelve::with_params(KT128, threads)
+    }
+    
+    fn with_params(params: Params, threads: usize) -> Self {
+        KangarooTwelve {
+            final_node: Sponge::new(params.rate, params.rounds),
+            leaf: Sponge::new(params.rate, params.rounds),
+            chunk_len: 0,
+            leaves: 0,
+            threads: max(threads, 1),
+            params,
+        }
+    }
+    
+    /// Append `input` to the message
+    pub fn update(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            if self.chunk_len == CHUNK {
+                // More input, hence the current chunk is not the last
+                if self.leaves == 0 {
+                    self.final_node.absorb(&[3, 0, 0, 0, 0, 0, 0, 0]);
+                } else {
+                    self.finish_leaf();
+                }
+                self.leaves += 1;
+                self.chunk_len = 0;
+            }
             
-            // χ
-            FOR5!(y, 5, {
-                FOR5!(x, 1, {
-                    c[x] = lanes[x + y];
-                });
-                FOR5!(x, 1, {
-                    lanes[x + y] = c[x] ^((!c[(x+1) % 5]) & c[(x+2)%5]);
-                });
-            });
+            if self.leaves > 0 && self.chunk_len == 0 {
+                // The current leaf is empty: process whole leaves in bulk
+                let mut n = input.len() / CHUNK;
+                // spawning threads costs about as much as hashing a few leaves
+                if self.threads > 1 && n >= 4 * self.threads {
+                    self.parallel_leaves(&input[0..n * CHUNK]);
+                } else {
+                    n -= n % 4;
+                    for group in input[0..n * CHUNK].chunks(4 * CHUNK) {
+                        for cv in &leaf_cvs_x4(group, self.params) {
+                            self.final_node.absorb(&cv[0..self.params.cv_len]);
+                        }
+                    }
+                }
+                if n > 0 {
+                    self.leaves += n;
+                    input = &input[n * CHUNK..];
+                    continue;
+                }
+            }
             
-            // ι
-            lanes[0] ^= RC[round];
+            let len = min(CHUNK - self.chunk_len, input.len());
+            if self.leaves == 0 {
+                self.final_node.absorb(&input[0..len]);
+            } else {
+                self.leaf.absorb(&input[0..len]);
+            }
+            self.chunk_len += len;
+            input = &input[len..];
+        }
+    }
+    
+    /// Absorb chaining values of the whole leaves in `input` into the final
+    /// node, computing these in parallel.
+    fn parallel_leaves(&mut self, input: &[u8]) {
+        let n = input.len() / CHUNK;
+        let params = self.params;
+        let mut cvs = vec![[0u8; CV_LEN_256]; n];
+        // whole groups of four leaves per thread, to use `leaf_cvs_x4`
+        let per_thread = n.div_ceil(self.threads).div_ceil(4) * 4;
+        thread::scope(|scope| {
+            let work = input.chunks(per_thread * CHUNK).zip(cvs.chunks_mut(per_thread));
+            for (input, cvs) in work {
+                scope.spawn(move || leaf_cvs(input, cvs, params));
+            }
+        });
+        for cv in &cvs {
+            self.final_node.absorb(&cv[0..params.cv_len]);
+        }
+    }
+    
+    /// Absorb the chaining value of the current leaf into the final node
+    fn finish_leaf(&mut self) {
+        let cv_len = self.params.cv_len;
+        let mut cv = [0u8; CV_LEN_256];
+        self.leaf.pad(0x0B);
+        self.leaf.squeeze(&mut cv[0..cv_len]);
+        self.final_node.absorb(&cv[0..cv_len]);
+        self.leaf = Sponge::new(self.params.rate, self.params.rounds);
+    }
+    
+    /// Finish the message with the given `customization` string and write
+    /// `output.len()` bytes of hash to `output`.
+    pub fn finalize<T: AsRef<[u8]>>(self, customization: T, output: &mut [u8]) {
+        self.finalize_xof(customization).squeeze(output);
+    }
+    
+    /// Finish the message with the given `customization` string and return
+    /// a reader for an unbounded amount of output.
+    pub fn finalize_xof<T: AsRef<[u8]>>(mut self, customization: T) -> KangarooTwelveReader {
+        let customization = customization.as_ref();
+        self.update(customization);
+        self.update(right_encode(customization.len(), &mut [0; 9]));
+        
+        if self.leaves == 0 {
+            // === Process the tree with only a final node ===
+            self.final_node.pad(0x07);
+        } else {
+            // === Process the tree with kangaroo hopping ===
+            self.finish_leaf();
+            self.final_node.absorb(right_encode(self.leaves, &mut [0; 9]));
+            self.final_node.absorb(b"\xFF\xFF");
+            self.final_node.pad(0x06);
         }
+        KangarooTwelveReader::new(self.final_node)
     }
 }
 
-fn read_u64(bytes: &[u8; 8]) -> u64 {
-    unsafe{ *(bytes as *const [u8; 8] as *const u64) }.to_le()
-}
-fn write_u64(val: u64) -> [u8; 8] {
-    unsafe{ *(&val.to_le() as *const u64 as *const [u8; 8]) }
+impl Default for KangarooTwelve {
+    fn default() -> Self {
+        KangarooTwelve::new()
+    }
 }
 
-fn keccak(state: &mut [u8; 200]) {
-    let mut lanes = [0u64; 25];
-    let mut y;
-    for x in 0..5 {
-        FOR5!(y, 5, {
-            lanes[x + y] = read_u64(array_ref!(state, 8*(x+y), 8));
-        });
+/// Streaming KT256 hasher: KangarooTwelve with 256-bit security.
+/// 
+/// This uses the same tree mode as `KangarooTwelve` (KT128), but built on
+/// TurboSHAKE256 with 64-byte chaining values.
+#[derive(Clone)]
+pub struct KangarooTwelve256(KangarooTwelve);
+
+impl KangarooTwelve256 {
+    /// Create a new hasher
+    pub fn new() -> Self {
+        KangarooTwelve256::with_threads(1)
     }
-    lanes::keccak(&mut lanes);
-    for x in 0..5 {
-        FOR5!(y, 5, {
-            let i = 8*(x+y);
-            state[i..i+8].copy_from_slice(&write_u64(lanes[x + y]));
-        });
+    
+    /// Create a new hasher using up to `threads` threads; see
+    /// `KangarooTwelve::with_threads`.
+    pub fn with_threads(threads: usize) -> Self {
+        KangarooTwelve256(KangarooTwelve::with_params(KT256, threads))
+    }
+    
+    /// Append `input` to the message
+    pub fn update(&mut self, input: &[u8]) {
+        self.0.update(input);
+    }
+    
+    /// Finish the message with the given `customization` string and write
+    /// `output.len()` bytes of hash to `output`.
+    pub fn finalize<T: AsRef<[u8]>>(self, customization: T, output: &mut [u8]) {
+        self.0.finalize(customization, output);
+    }
+    
+    /// Finish the message with the given `customization` string and return
+    /// a reader for an unbounded amount of output.
+    pub fn finalize_xof<T: AsRef<[u8]>>(self, customization: T) -> KangarooTwelveReader {
+        self.0.finalize_xof(customization)
     }
 }
 
-fn f(input: &[u8], suffix: u8, mut output_len: usize) -> Vec<u8> {
-    let mut state = [0u8; 200];
-    let max_block_size = 1344 / 8;  // r, also known as rate in bytes
-    
-    // === Absorb all the input blocks ===
-    // We unroll first loop, which allows simple copy
-    let mut block_size = min(input.len(), max_block_size);
-    state[0..block_size].copy_from_slice(&input[0..block_size]);
-    
-    let mut offset = block_size;
-    while offset < input.len() {
-        keccak(&mut state);
-        block_size = min(input.len() - offset, max_block_size);
-        for i in 0..block_size {
-            // TODO: is this sufficiently optimisable or better to convert to u64 first?
-            state[i] ^= input[i+offset];
-        }
-        offset += block_size;
-    }
-    if block_size == max_block_size {
-        // TODO: condition is nearly always false; tests pass without this.
-        // Why is it here?
-        keccak(&mut state);
-        block_size = 0;
-    }
-    
-    // === Do the padding and switch to the squeezing phase ===
-    state[block_size] ^= suffix;
-    if ((suffix & 0x80) != 0) && (block_size == (max_block_size-1)) {
-        // TODO: condition is almost always false — in fact tests pass without
-        // this block! So why is it here?
-        keccak(&mut state);
-    }
-    state[max_block_size-1] ^= 0x80;
-    keccak(&mut state);
-    
-    // === Squeeze out all the output blocks ===
-    let mut output = Vec::with_capacity(output_len);
-    while output_len > 0 {
-        block_size = min(output_len, max_block_size);
-        output.extend_from_slice(&state[0..block_size]);
-        output_len -= block_size;
-        if output_len > 0 {
-            keccak(&mut state);
-        }
+impl Default for KangarooTwelve256 {
+    fn default() -> Self {
+        KangarooTwelve256::new()
     }
-    output
 }
 
-fn right_encode(mut x: usize) -> Vec<u8> {
-    let mut slice = Vec::new();
-    while x > 0 {
-        slice.push((x % 256) as u8);
-        x /= 256;
+/// Extendable output of KangarooTwelve.
+/// 
+/// Reading output in several pieces gives the same result as reading it all
+/// at once.
+pub type KangarooTwelveReader = SpongeReader;
+
+macro_rules! turbo_shake {
+    ($name:ident, $rate:expr, $doc:expr) => {
+        #[doc = $doc]
+        /// 
+        /// The domain separation byte must be in the range `0x01..=0x7F`;
+        /// `Default` uses `0x1F`.
+        #[derive(Clone)]
+        pub struct $name {
+            sponge: Sponge,
+            domain: u8,
+        }
+        
+        impl $name {
+            /// Create a new instance with the given domain separation byte.
+            /// 
+            /// Panics if `domain` is not in the range `0x01..=0x7F`.
+            pub fn new(domain: u8) -> Self {
+                assert!((0x01..=0x7F).contains(&domain), "TurboSHAKE domain byte out of range");
+                $name { sponge: Sponge::new($rate, ROUNDS), domain }
+            }
+            
+            /// Append `input` to the message
+            pub fn update(&mut self, input: &[u8]) {
+                self.sponge.absorb(input);
+            }
+            
+            /// Finish the message and write `output.len()` bytes of hash to
+            /// `output`.
+            pub fn finalize(self, output: &mut [u8]) {
+                self.finalize_xof().squeeze(output);
+            }
+            
+            /// Finish the message and return a reader for an unbounded
+            /// amount of output.
+            pub fn finalize_xof(mut self) -> TurboShakeReader {
+                self.sponge.pad(self.domain);
+                TurboShakeReader::new(self.sponge)
+            }
+        }
+        
+        impl Default for $name {
+            fn default() -> Self {
+                $name::new(0x1F)
+            }
+        }
     }
-    slice.reverse();
-    let len = slice.len();
-    slice.push(len as u8);
-    slice
 }
 
+turbo_shake!(TurboShake128, RATE, "TurboSHAKE128 (RFC 9861), the sponge underlying KangarooTwelve.");
+turbo_shake!(TurboShake256, RATE_256, "TurboSHAKE256 (RFC 9861): as `TurboShake128` with capacity 512.");
+
+/// Extendable output of `TurboShake128` or `TurboShake256`.
+pub type TurboShakeReader = SpongeReader;
+
 /// Hash the `input` message, with the given `customization` string, to `output_len` bytes.
 pub fn kangaroo_twelve<TA: AsRef<[u8]>, TB: AsRef<[u8]>>(input: TA,
         customization: TB, output_len: usize) -> Vec<u8>
 {
-    let b = 8192;
-    let c = 256;
-    
-    let mut slice = Vec::new(); // S
-    slice.extend_from_slice(input.as_ref());
-    slice.extend_from_slice(customization.as_ref());
-    slice.extend_from_slice(&right_encode(customization.as_ref().len())[..]);
-    
-    // === Cut the input string into chunks of b bytes ===
-    let n = (slice.len() + b - 1) / b;
-    let mut slices = Vec::with_capacity(n); // Si
-    for i in 0..n {
-        let ub = min((i+1)*b, slice.len());
-        slices.push(&slice[i*b .. ub]);
-    }
-    
-    if n == 1 {
-        // === Process the tree with only a final node ===
-        f(slices[0], 0x07, output_len)
-    } else {
-        // === Process the tree with kangaroo hopping ===
-        // TODO: in parallel
-        let mut intermediate = Vec::with_capacity(n-1); // CVi
-        for i in 0..n-1 {
-            intermediate.push(f(slices[i+1], 0x0B, c/8));
-        }
-        
-        let mut node_star = Vec::new();
-        node_star.extend_from_slice(slices[0]);
-        node_star.extend_from_slice(&[3,0,0,0,0,0,0,0]);
-        for i in 0..n-1 {
-            node_star.extend_from_slice(&intermediate[i][..]);
-        }
-        node_star.extend_from_slice(&right_encode(n-1));
-        node_star.extend_from_slice(b"\xFF\xFF");
-        
-        f(&node_star[..], 0x06, output_len)
-    }
+    let mut output = vec![0; output_len];
+    kangaroo_twelve_into(input, customization, &mut output);
+    output
+}
+
+/// Hash the `input` message, with the given `customization` string, to
+/// `output.len()` bytes written to `output`.
+/// 
+/// This does not allocate.
+pub fn kangaroo_twelve_into<TA: AsRef<[u8]>, TB: AsRef<[u8]>>(input: TA,
+        customization: TB, output: &mut [u8])
+{
+    let mut hasher = KangarooTwelve::new();
+    hasher.update(input.as_ref());
+    hasher.finalize(customization, output);
+}
+
+/// Hash the `input` message with KT256, with the given `customization`
+/// string, to `output_len` bytes.
+pub fn kangaroo_twelve_256<TA: AsRef<[u8]>, TB: AsRef<[u8]>>(input: TA,
+        customization: TB, output_len: usize) -> Vec<u8>
+{
+    let mut output = vec![0; output_len];
+    kangaroo_twelve_256_into(input, customization, &mut output);
+    output
+}
+
+/// Hash the `input` message with KT256, with the given `customization`
+/// string, to `output.len()` bytes written to `output`.
+/// 
+/// This does not allocate.
+pub fn kangaroo_twelve_256_into<TA: AsRef<[u8]>, TB: AsRef<[u8]>>(input: TA,
+        customization: TB, output: &mut [u8])
+{
+    let mut hasher = KangarooTwelve256::new();
+    hasher.update(input.as_ref());
+    hasher.finalize(customization, output);
+}
+
+/// Hash the `input` message with MarsupilamiFourteen, with the given
+/// `customization` string, to `output_len` bytes.
+/// 
+/// M14 is the variant of KangarooTwelve from the same paper using 14 rounds
+/// of Keccak-p, for 256-bit security with 64-byte chaining values.
+pub fn m14<TA: AsRef<[u8]>, TB: AsRef<[u8]>>(input: TA,
+        customization: TB, output_len: usize) -> Vec<u8>
+{
+    let mut hasher = KangarooTwelve::with_params(M14, 1);
+    hasher.update(input.as_ref());
+    let mut output = vec![0; output_len];
+    hasher.finalize(customization, &mut output);
+    output
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::io;
     use std::iter;
-    
-    fn read_bytes<T: AsRef<[u8]>>(s: T) -> Vec<u8> {
-        fn b(c: u8) -> u8 {
-            match c {
-                b'0' ... b'9' => c - b'0',
-                b'a' ... b'f' => c - b'a' + 10,
-                b'A' ... b'F' => c - b'A' + 10,
-                _ => unreachable!(),
-            }
-        }
-        let s = s.as_ref();
-        let mut i = 0;
-        let mut v = Vec::new();
-        while i < s.len() {
-            if s[i] == b' ' || s[i] == b'\n' { i += 1; continue; }
-            
-            let n = b(s[i]) * 16 + b(s[i+1]);
-            v.push(n);
-            i += 2;
-        }
-        v
-    }
+    use util::read_bytes;
     
     #[test]
     fn empty() {
@@ -318,7 +512,7 @@ mod test {
                 ee df 88 3e b6 78 a0 a8 e0 37 16 82",
                 "3c 39 07 82 a8 a4 e8 9f a6 36 7f 72 fe aa f1 32 55 c8 d9 58
                 78 48 1d 3c d8 ce 85 f5 8e 88 0a f8"];
-        for i in 0..5 /*NOTE: can be up to 7 but is slow*/ {
+        for i in 0..7 {
             let len = 17usize.pow(i);
             let m: Vec<u8> = (0..len).map(|j| (j % 251) as u8).collect();
             let result = kangaroo_twelve(m, "", 32);
@@ -326,6 +520,26 @@ mod test {
         }
     }
     
+    #[test]
+    fn chunk_boundaries() {
+        // Source: RFC 9861; S = M || C || right_encode(|C|) is either side
+        // of the 8192-byte chunk size
+        let expected = [
+                (8191, 0, "1B 57 76 36 F7 23 64 3E 99 0C C7 D6 A6 59 83 74 36 FD 6A 10
+                36 26 60 0E B8 30 1C D1 DB E5 53 D6"),
+                (8192, 0, "48 F2 56 F6 77 2F 9E DF B6 A8 B6 61 EC 92 DC 93 B9 5E BD 05
+                A0 8A 17 B3 9A E3 49 08 70 C9 26 C3"),
+                (8192, 8189, "3E D1 2F 70 FB 05 DD B5 86 89 51 0A B3 E4 D2 3C 6C 60 33 84
+                9A A0 1E 1D 8C 22 0A 29 7F ED CD 0B"),
+                (8192, 8190, "6A 7C 1B 6A 5C D0 D8 C9 CA 94 3A 4A 21 6C C6 46 04 55 9A 2E
+                A4 5F 78 57 0A 15 25 3D 67 BA 00 AE")];
+        for &(m_len, c_len, hash) in &expected {
+            let m: Vec<u8> = (0..m_len).map(|j| (j % 251) as u8).collect();
+            let c: Vec<u8> = (0..c_len).map(|j| (j % 251) as u8).collect();
+            assert_eq!(kangaroo_twelve(&m, &c, 32), read_bytes(hash), "{}, {}", m_len, c_len);
+        }
+    }
+    
     #[test]
     fn pat_c() {
         let expected = [
@@ -345,4 +559,372 @@ mod test {
             assert_eq!(result, read_bytes(expected[i as usize]));
         }
     }
+    
+    fn turbo_shake_128(m: &[u8], domain: u8, len: usize) -> Vec<u8> {
+        let mut ts = TurboShake128::new(domain);
+        ts.update(m);
+        let mut output = vec![0; len];
+        ts.finalize(&mut output);
+        output
+    }
+    
+    fn turbo_shake_256(m: &[u8], domain: u8, len: usize) -> Vec<u8> {
+        let mut ts = TurboShake256::new(domain);
+        ts.update(m);
+        let mut output = vec![0; len];
+        ts.finalize(&mut output);
+        output
+    }
+    
+    #[test]
+    fn turbo_shake_128_vectors() {
+        // Source: RFC 9861
+        assert_eq!(turbo_shake_128(b"", 0x1F, 32), read_bytes("1E 41 5F 1C 59 83 AF F2 16 92 17 27
+                7D 17 BB 53 8C D9 45 A3 97 DD EC 54 1F 1C E4 1A F2 C1 B7 4C"));
+        assert_eq!(turbo_shake_128(b"", 0x1F, 64), read_bytes("1E 41 5F 1C 59 83 AF F2 16 92 17 27
+                7D 17 BB 53 8C D9 45 A3 97 DD EC 54 1F 1C E4 1A F2 C1 B7 4C 3E 8C CA E2 A4 DA
+                E5 6C 84 A0 4C 23 85 C0 3C 15 E8 19 3B DF 58 73 73 63 32 16 91 C0 54 62 C8 DF"));
+        assert_eq!(turbo_shake_128(b"", 0x1F, 10032)[10000..], read_bytes("A3 B9 B0 38 59 00 CE 76
+                1F 22 AE D5 48 E7 54 DA 10 A5 24 2D 62 E8 C6 58 E3 F3 A9 23 A7 55 56 07")[..]);
+        assert_eq!(turbo_shake_128(b"\x00", 0x1F, 32), read_bytes("55 CE DD 6F 60 AF 7B B2 9A 40
+                42 AE 83 2E F3 F5 8D B7 29 9F 89 3E BB 92 47 24 7D 85 69 58 DA A9"));
+        assert_eq!(turbo_shake_128(b"\xFF\xFF\xFF", 0x01, 32), read_bytes("BF 32 3F 94 04 94 E8 8E
+                E1 C5 40 FE 66 0B E8 A0 C9 3F 43 D1 5E C0 06 99 84 62 FA 99 4E ED 5D AB"));
+        assert_eq!(turbo_shake_128(b"\xFF", 0x06, 32), read_bytes("8E C9 C6 64 65 ED 0D 4A 6C 35
+                D1 35 06 71 8D 68 7A 25 CB 05 C7 4C CA 1E 42 50 1A BD 83 87 4A 67"));
+        assert_eq!(turbo_shake_128(b"\xFF\xFF\xFF", 0x07, 32), read_bytes("B6 58 57 60 01 CA D9 B1
+                E5 F3 99 A9 F7 77 23 BB A0 54 58 04 2D 68 20 6F 72 52 68 2D BA 36 63 ED"));
+    }
+    
+    #[test]
+    fn turbo_shake_256_vectors() {
+        // Source: RFC 9861
+        assert_eq!(turbo_shake_256(b"", 0x1F, 64), read_bytes("36 7A 32 9D AF EA 87 1C 78 02 EC 67
+                F9 05 AE 13 C5 76 95 DC 2C 66 63 C6 10 35 F5 9A 18 F8 E7 DB 11 ED C0 E1 2E 91
+                EA 60 EB 6B 32 DF 06 DD 7F 00 2F BA FA BB 6E 13 EC 1C C2 0D 99 55 47 60 0D B0"));
+        assert_eq!(turbo_shake_256(b"\x00", 0x1F, 64), read_bytes("3E 17 12 F9 28 F8 EA F1 05 46
+                32 B2 AA 0A 24 6E D8 B0 C3 78 72 8F 60 BC 97 04 10 15 5C 28 82 0E 90 CC 90 D8
+                A3 00 6A A2 37 2C 5C 5E A1 76 B0 68 2B F2 2B AE 74 67 AC 94 F7 4D 43 D3 9B 04
+                82 E2"));
+        assert_eq!(turbo_shake_256(b"\xFF\xFF\xFF", 0x01, 64), read_bytes("D2 1C 6F BB F5 87 FA 22
+                82 F2 9A EA 62 01 75 FB 02 57 41 3A F7 8A 0B 1B 2A 87 41 9C E0 31 D9 33 AE 7A
+                4D 38 33 27 A8 A1 76 41 A3 4F 8A 1D 10 03 AD 7D A6 B7 2D BA 84 BB 62 FE F2 8F
+                62 F1 24 24"));
+    }
+    
+    #[test]
+    fn turbo_shake_streaming() {
+        let m: Vec<u8> = (0..1000).map(|j| (j % 251) as u8).collect();
+        let expected = turbo_shake_256(&m, 0x0A, 1000);
+        for &step in &[1, 7, 135, 136, 137, 1000] {
+            let mut ts = TurboShake256::new(0x0A);
+            for piece in m.chunks(step) {
+                ts.update(piece);
+            }
+            let mut reader = ts.finalize_xof();
+            let mut output = vec![0u8; 1000];
+            for piece in output.chunks_mut(step) {
+                reader.squeeze(piece);
+            }
+            assert_eq!(output, expected, "step {}", step);
+        }
+        assert_ne!(turbo_shake_128(&m, 0x0A, 32), turbo_shake_128(&m, 0x0B, 32));
+    }
+    
+    #[test]
+    #[should_panic]
+    fn turbo_shake_domain() {
+        TurboShake128::new(0x80);
+    }
+    
+    #[test]
+    fn kt256() {
+        // Source: RFC 9861
+        assert_eq!(kangaroo_twelve_256("", "", 64), read_bytes("B2 3D 2E 9C EA 9F 49 04 E0 2B EC 06
+                81 7F C1 0C E3 8C E8 E9 3E F4 C8 9E 65 37 07 6A F8 64 64 04 E3 E8 B6 81 07 B8
+                83 3A 5D 30 49 0A A3 34 82 35 3F D4 AD C7 14 8E CB 78 28 55 00 3A AE BD E4 A9"));
+        assert_eq!(kangaroo_twelve_256("", "", 10064)[10000..], read_bytes("AD 4A 1D 71 8C F9 50 50
+                67 09 A4 C3 33 96 13 9B 44 49 04 1F C7 9A 05 D6 8D A3 5F 1E 45 35 22 E0 56 C6
+                4F E9 49 58 E7 08 5F 29 64 88 82 59 B9 93 27 52 F3 CC D8 55 28 8E FE E5 FC BB
+                8B 56 30 69")[..]);
+        assert_eq!(kangaroo_twelve_256(b"\x00", "", 64), read_bytes("0D 00 5A 19 40 85 36 02 17 12
+                8C F1 7F 91 E1 F7 13 14 EF A5 56 45 39 D4 44 91 2E 34 37 EF A1 7F 82 DB 6F 6F
+                FE 76 E7 81 EA A0 68 BC E0 1F 2B BF 81 EA CB 98 3D 72 30 F2 FB 02 83 4A 21 B1
+                DD D0"));
+    }
+    
+    #[test]
+    fn kt256_pat_m() {
+        // Source: RFC 9861
+        let expected = [
+                "0d 00 5a 19 40 85 36 02 17 12 8c f1 7f 91 e1 f7 13 14 ef a5
+                56 45 39 d4 44 91 2e 34 37 ef a1 7f 82 db 6f 6f fe 76 e7 81
+                ea a0 68 bc e0 1f 2b bf 81 ea cb 98 3d 72 30 f2 fb 02 83 4a
+                21 b1 dd d0",
+                "1b a3 c0 2b 1f c5 14 47 4f 06 c8 97 99 78 a9 05 6c 84 83 f4
+                a1 b6 3d 0d cc ef e3 a2 8a 2f 32 3e 1c dc ca 40 eb f0 06 ac
+                76 ef 03 97 15 23 46 83 7b 12 77 d3 e7 fa a9 c9 65 3b 19 07
+                50 98 52 7b",
+                "de 8c cb c6 3e 0f 13 3e bb 44 16 81 4d 4c 66 f6 91 bb f8 b6
+                a6 1e c0 a7 70 0f 83 6b 08 6c b0 29 d5 4f 12 ac 71 59 47 2c
+                72 db 11 8c 35 b4 e6 aa 21 3c 65 62 ca aa 9d cc 51 89 59 e6
+                9b 10 f3 ba",
+                "64 7e fb 49 fe 9d 71 75 00 17 1b 41 e7 f1 1b d4 91 54 44 43
+                20 99 97 ce 1c 25 30 d1 5e b1 ff bb 59 89 35 ef 95 45 28 ff
+                c1 52 b1 e4 d7 31 ee 26 83 68 06 74 36 5c d1 91 d5 62 ba e7
+                53 b8 4a a5",
+                "b0 62 75 d2 84 cd 1c f2 05 bc be 57 dc cd 3e c1 ff 66 86 e3
+                ed 15 77 63 83 e1 f2 fa 3c 6a c8 f0 8b f8 a1 62 82 9d b1 a4
+                4b 2a 43 ff 83 dd 89 c3 cf 1c eb 61 ed e6 59 76 6d 5c cf 81
+                7a 62 ba 8d",
+                "94 73 83 1d 76 a4 c7 bf 77 ac e4 5b 59 f1 45 8b 16 73 d6 4b
+                cd 87 7a 7c 66 b2 66 4a a6 dd 14 9e 60 ea b7 1b 5c 2b ab 85
+                8c 07 4d ed 81 dd ce 2b 40 22 b5 21 59 35 c0 d4 d1 9b f5 11
+                ae eb 07 72",
+                "06 52 b7 40 d7 8c 5e 1f 7c 8d cc 17 77 09 73 82 76 8b 7f f3
+                8f 9a 7a 20 f2 9f 41 3b b1 b3 04 5b 31 a5 57 8f 56 8f 91 1e
+                09 cf 44 74 6d a8 42 24 a5 26 6e 96 a4 a5 35 e8 71 32 4e 4f
+                9c 70 04 da"];
+        for i in 0..7 {
+            let len = 17usize.pow(i);
+            let m: Vec<u8> = (0..len).map(|j| (j % 251) as u8).collect();
+            let result = kangaroo_twelve_256(m, "", 64);
+            assert_eq!(result, read_bytes(expected[i as usize]));
+        }
+    }
+    
+    #[test]
+    fn kt256_chunk_boundaries() {
+        // Source: RFC 9861
+        let expected = [
+                (8191, 0, "30 81 43 4d 93 a4 10 8d 8d 8a 33 05 b8 96 82 ce be dc 7c a4
+                ea 8a 3c e8 69 fb b7 3c be 4a 58 ee f6 f2 4d e3 8f fc 17 05
+                14 c7 0e 7a b2 d0 1f 03 81 26 16 e8 63 d7 69 af b3 75 31 93
+                ba 04 5b 20"),
+                (8192, 0, "c6 ee 8e 2a d3 20 0c 01 8a c8 7a aa 03 1c da c2 21 21 b4 12
+                d0 7d c6 e0 dc cb b5 34 23 74 7e 9a 1c 18 83 4d 99 df 59 6c
+                f0 cf 4b 8d fa fb 7b f0 2d 13 9d 0c 90 35 72 5a dc 1a 01 b7
+                23 0a 41 fa"),
+                (8192, 8189, "74 e4 78 79 f1 0a 9c 5d 11 bd 2d a7 e1 94 fe 57 e8 63 78 bf
+                3c 3f 74 48 ef f3 c5 76 a0 f1 8c 5c aa e0 99 99 79 51 20 90
+                a7 f3 48 af 42 60 d4 de 3c 37 f1 ec af 8d 2c 2c 96 c1 d1 6c
+                64 b1 24 96"),
+                (8192, 8190, "f4 b5 90 8b 92 9f fe 01 e0 f7 9e c2 f2 12 43 d4 1a 39 6b 2e
+                73 03 a6 af 1d 63 99 cd 6c 7a 0a 2d d7 c4 f6 07 e8 27 7f 9c
+                9b 1c b4 ab 9d dc 59 d4 b9 2d 1f c7 55 84 41 f1 83 2c 32 79
+                a4 24 1b 8b")];
+        for &(m_len, c_len, hash) in &expected {
+            let m: Vec<u8> = (0..m_len).map(|j| (j % 251) as u8).collect();
+            let c: Vec<u8> = (0..c_len).map(|j| (j % 251) as u8).collect();
+            assert_eq!(kangaroo_twelve_256(&m, &c, 64), read_bytes(hash), "{}, {}", m_len, c_len);
+        }
+    }
+    
+    #[test]
+    fn kt256_pat_c() {
+        // Source: RFC 9861
+        let expected = [
+                "92 80 f5 cc 39 b5 4a 5a 59 4e c6 3d e0 bb 99 37 1e 46 09 d4
+                4b f8 45 c2 f5 b8 c3 16 d7 2b 15 98 11 f7 48 f2 3e 3f ab be
+                5c 32 26 ec 96 c6 21 86 df 2d 33 e9 df 74 c5 06 9c ee cb b4
+                dd 10 ef f6",
+                "47 ef 96 dd 61 6f 20 09 37 aa 78 47 e3 4e c2 fe ae 80 87 e3
+                76 1d c0 f8 c1 a1 54 f5 1d c9 cc f8 45 d7 ad bc e5 7f f6 4b
+                63 97 22 c6 a1 67 2e 3b f5 37 2d 87 e0 0a ff 89 be 97 24 07
+                56 99 88 53",
+                "3b 48 66 7a 50 51 c5 96 6c 53 c5 d4 2b 95 de 45 1e 05 58 4e
+                78 06 e2 fb 76 5e da 95 90 74 17 2c b4 38 a9 e9 1d de 33 7c
+                98 e9 c4 1b ed 94 c4 e0 ae f4 31 d0 b6 4e f2 32 4f 79 32 ca
+                a6 f5 49 69",
+                "e0 91 1c c0 00 25 e1 54 08 31 e2 66 d9 4a dd 9b 98 71 21 42
+                b8 0d 26 29 e6 43 aa c4 ef af 5a 3a 30 a8 8c bf 4a c2 a9 1a
+                24 32 74 30 54 fb cc 98 97 67 0e 86 ba 8c ec 2f c2 ac e9 c9
+                66 36 97 24"];
+        for i in 0..4 {
+            let m: Vec<u8> = vec![0xFF; 2usize.pow(i) - 1];
+            let len = 41usize.pow(i);
+            let c: Vec<u8> = (0..len).map(|j| (j % 251) as u8).collect();
+            let result = kangaroo_twelve_256(m, c, 64);
+            assert_eq!(result, read_bytes(expected[i as usize]));
+        }
+    }
+    
+    #[test]
+    fn kt256_streaming() {
+        let m: Vec<u8> = (0..10 * 8192 + 100).map(|j| (j % 251) as u8).collect();
+        for &len in &[0, 8191, 8192, 8193, 5 * 8192, m.len()] {
+            let expected = kangaroo_twelve_256(&m[0..len], "custom", 64);
+            for &step in &[1000, 8192, 4 * 8192 + 1] {
+                for &threads in &[1, 3] {
+                    let mut hasher = KangarooTwelve256::with_threads(threads);
+                    for piece in m[0..len].chunks(step) {
+                        hasher.update(piece);
+                    }
+                    let mut output = [0u8; 64];
+                    hasher.finalize("custom", &mut output);
+                    assert_eq!(output[..], expected[..], "len {}, step {}", len, step);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn m14() {
+        // Build the tree for S = M || right_encode(0), using three chunks
+        let m: Vec<u8> = (0..3 * 8192 - 1).map(|j| (j % 251) as u8).collect();
+        let mut s = m.clone();
+        s.push(0);
+        let mut final_node = Sponge::new(RATE_256, 14);
+        final_node.absorb(&s[0..8192]);
+        final_node.absorb(&[3, 0, 0, 0, 0, 0, 0, 0]);
+        for chunk in s[8192..].chunks(8192) {
+            let mut leaf = Sponge::new(RATE_256, 14);
+            leaf.absorb(chunk);
+            leaf.pad(0x0B);
+            let mut cv = [0u8; 64];
+            leaf.squeeze(&mut cv);
+            final_node.absorb(&cv);
+        }
+        final_node.absorb(&[2, 1, 0xFF, 0xFF]);
+        final_node.pad(0x06);
+        let mut expected = [0u8; 32];
+        final_node.squeeze(&mut expected);
+        assert_eq!(super::m14(&m, "", 32)[..], expected[..]);
+        
+        assert_ne!(super::m14("", "", 32), kangaroo_twelve_256("", "", 32));
+    }
+    
+    #[test]
+    fn m14_pat_m() {
+        // Source: an independent byte-oriented implementation of the tree
+        // over RustCrypto's `keccak::p1600` at 14 rounds; at 12 rounds the
+        // same code reproduces all RFC 9861 KT128 and KT256 vectors.
+        let expected = [
+                "cc 05 eb c9 28 15 6c 7a 03 54 00 85 35 5c 47 c6 ae a1 d0 7d
+                c8 11 cd de d0 e4 c3 67 f8 d9 93 68 a5 31 82 5d 99 64 13 a9
+                bc 0e 1e 57 2f f5 df 4f 98 ca 65 f4 fb 49 00 ee 23 55 f5 95
+                99 e2 f6 48",
+                "aa 76 4f d8 b3 8f 19 97 6a 30 5c b0 07 f1 93 84 b2 10 a5 c7
+                b0 fc 44 99 d6 f8 3c 62 27 bf f8 50 27 0b 88 0c ff 3f 17 32
+                5b 84 3e 97 2a e0 b9 9a 25 fa 0e 00 50 cc 74 8f 37 c4 cf c2
+                59 2f d1 72",
+                "f1 8a 6e 25 0b 1c c8 3d ea 89 ff bb 4d e5 6a 8e 70 04 1c 71
+                fc 5b 17 a2 aa ab 05 c6 06 aa 6b f2 7c 39 55 c9 46 e8 e2 15
+                f0 b1 e2 c9 3c b9 e7 a7 36 c3 39 c0 6f 34 e5 87 df 3b cc 58
+                47 cf 25 f6",
+                "0a c8 9b 11 a0 6f 46 b2 f6 fe ef f0 46 c9 7e 90 dc 02 91 0a
+                e5 09 b8 73 9c fe a5 df 1d f9 0b 82 89 5a 5f ad 67 ad 2f a4
+                12 59 09 07 56 c0 d9 88 44 0f a3 26 7a 48 38 0a da 5d f9 c7
+                f0 29 07 57",
+                "35 af 0a 5f c6 c4 d1 11 fb c6 8f 87 9d 05 50 6a af d3 00 b5
+                ab 13 69 86 d7 ae d8 a9 f1 be 33 1e 86 64 38 18 64 67 2e 81
+                ba 32 d8 28 b2 c0 51 92 a5 88 68 46 f6 c7 57 0e 7e ba eb 97
+                b5 9b d7 3e",
+                "0c 98 2c 5d 53 34 e2 7c c6 59 1c da 30 8d fa 6b 4f dd 73 6a
+                ad be 64 53 6b de f8 3c 1d 49 6b a0 77 c8 00 d2 fd fd 56 4a
+                8b 8a f3 28 ec 47 73 1c 5b 82 fc 7f 92 fa 50 76 f6 07 e1 44
+                b5 2c db 4e"];
+        assert_eq!(super::m14("", "", 64), read_bytes("6f 66 ef 14 74 eb 53 80 7a a3 29 25 7c 76
+                8b b8 88 93 d9 f0 86 e5 1d a2 f5 c8 0d 17 ca 0f c5 7d 5a 24 fa c8 79 01 4f 8b
+                30 a3 fd f5 ac 56 eb af a2 19 eb 89 1d 4b bb ab 7e 1d f3 b2 72 05 b4 59"));
+        for i in 0..6 {
+            let len = 17usize.pow(i);
+            let m: Vec<u8> = (0..len).map(|j| (j % 251) as u8).collect();
+            let result = super::m14(m, "", 64);
+            assert_eq!(result, read_bytes(expected[i as usize]));
+        }
+        
+        // customization string, as in the RFC's KT256 vectors
+        let m = [0xFF; 7];
+        let c: Vec<u8> = (0..41usize.pow(3)).map(|j| (j % 251) as u8).collect();
+        assert_eq!(super::m14(m, c, 64), read_bytes("61 58 3c df aa 64 ab 60 e7 7b 8c 8b dd 0a
+                d0 88 f9 d7 60 b2 94 4f 7d 64 c5 dd 81 ce 7e 92 d9 6b ff 67 84 3a 1e ed 51 f3
+                01 db 51 ff 54 fd cd 44 62 fd 05 14 25 d4 c2 ed ba 74 ac 2b 15 32 ec 14"));
+    }
+    
+    #[test]
+    fn right_encode() {
+        let mut buf = [0; 9];
+        assert_eq!(super::right_encode(0, &mut buf), &[0]);
+        assert_eq!(super::right_encode(12, &mut buf), &[12, 1]);
+        assert_eq!(super::right_encode(65538, &mut buf), &[1, 0, 2, 3]);
+        assert_eq!(super::right_encode(!0, &mut buf).len(), 1 + 0usize.count_zeros() as usize / 8);
+    }
+    
+    #[test]
+    fn xof() {
+        let expected = read_bytes("e8 dc 56 36 42 f7 22 8c 84 68 4c 89 84 05 d3 a8
+                34 79 91 58 c0 79 b1 28 80 27 7a 1d 28 e2 ff 6d");
+        for &step in &[1, 7, 32, 167, 168, 169, 1000, 10032] {
+            let mut reader = KangarooTwelve::new().finalize_xof("");
+            let mut output = vec![0u8; 10032];
+            for piece in output.chunks_mut(step) {
+                reader.squeeze(piece);
+            }
+            assert_eq!(output[10000..], expected[..], "step {}", step);
+        }
+        
+        let mut reader = KangarooTwelve::new().finalize_xof("");
+        let mut output = vec![0u8; 10032];
+        let mut pos = 0;
+        let mut step = 0;
+        while pos < output.len() {
+            step = (step * 7 + 5) % 300;
+            let end = min(pos + step, output.len());
+            pos += io::Read::read(&mut reader, &mut output[pos..end]).unwrap();
+        }
+        assert_eq!(output, kangaroo_twelve("", "", 10032));
+    }
+    
+    #[test]
+    fn leaf_cvs_x4() {
+        let m: Vec<u8> = (0..4 * 8192).map(|j| (j % 253) as u8).collect();
+        for &params in &[KT128, KT256, M14] {
+            let cvs = super::leaf_cvs_x4(&m, params);
+            for k in 0..4 {
+                assert_eq!(cvs[k][..], leaf_cv(&m[k * 8192 .. (k + 1) * 8192], params)[..]);
+            }
+        }
+    }
+    
+    #[test]
+    fn threads() {
+        let m: Vec<u8> = (0..67 * 8192 + 5).map(|j| (j % 251) as u8).collect();
+        for &len in &[8192, 2 * 8192, 3 * 8192 + 1, 9 * 8192 + 5, 14 * 8192, 67 * 8192 + 5] {
+            let m = &m[0..len];
+            let expected = kangaroo_twelve(m, "xyz", 32);
+            for &threads in &[2, 3, 4, 16] {
+                for &step in &[1000, 3 * 8192, len] {
+                    let mut hasher = KangarooTwelve::with_threads(threads);
+                    for piece in m.chunks(step) {
+                        hasher.update(piece);
+                    }
+                    let mut output = [0u8; 32];
+                    hasher.finalize("xyz", &mut output);
+                    assert_eq!(&output[..], &expected[..],
+                            "len {}, threads {}, step {}", len, threads, step);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn streaming() {
+        let m: Vec<u8> = (0..3 * 8192 + 100).map(|j| (j % 251) as u8).collect();
+        for &len in &[0, 1, 8191, 8192, 8193, 2 * 8192, 3 * 8192 + 100] {
+            let m = &m[0..len];
+            for &c in &["", "abc"] {
+                let expected = kangaroo_twelve(m, c, 40);
+                for &step in &[1, 167, 168, 1000, 8192, 8193] {
+                    let mut hasher = KangarooTwelve::new();
+                    for piece in m.chunks(step) {
+                        hasher.update(piece);
+                    }
+                    let mut output = [0u8; 40];
+                    hasher.finalize(c, &mut output);
+                    assert_eq!(&output[..], &expected[..], "len {}, step {}", len, step);
+                }
+            }
+        }
+    }
 }
@@ -9,6 +9,9 @@
 
 use std::cmp::min;
 
+use k12::{kangaroo_twelve_into, right_encode};
+use keccak_p::keccak_p1600;
+
 #[macro_use]
 mod macros {
     /// Copied from `arrayref` crate
@@ -27,176 +30,137 @@ mod macros {
             }
         }}
     }
-
-    macro_rules! REPEAT4 {
-        ($e: expr) => ( $e; $e; $e; $e; )
-    }
-
-    macro_rules! REPEAT5 {
-        ($e: expr) => ( $e; $e; $e; $e; $e; )
-    }
-
-    macro_rules! REPEAT6 {
-        ($e: expr) => ( $e; $e; $e; $e; $e; $e; )
-    }
-
-    macro_rules! REPEAT24 {
-        ($e: expr, $s: expr) => (
-            REPEAT6!({ $e; $s; });
-            REPEAT6!({ $e; $s; });
-            REPEAT6!({ $e; $s; });
-            REPEAT5!({ $e; $s; });
-            $e;
-        )
-    }
-
-    macro_rules! FOR5 {
-        ($v: expr, $s: expr, $e: expr) => {
-            $v = 0;
-            REPEAT4!({
-                $e;
-                $v += $s;
-            });
-            $e;
-        }
-    }
-}
-
-mod lanes {
-    pub const RC: [u64; 12] = [
-        0x000000008000808b,
-        0x800000000000008b,
-        0x8000000000008089,
-        0x8000000000008003,
-        0x8000000000008002,
-        0x8000000000000080,
-        0x000000000000800a,
-        0x800000008000000a,
-        0x8000000080008081,
-        0x8000000000008080,
-        0x0000000080000001,
-        0x8000000080008008,
-    ];
-
-    // (0..24).map(|t| ((t+1)*(t+2)/2) % 64)
-    pub const RHO: [u32; 24] = [
-        1, 3, 6, 10, 15, 21,28, 36, 45, 55, 2, 14, 27,
-        41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44
-    ];
-    pub const PI: [usize; 24] = [
-        10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23,
-        19, 13, 12, 2, 20, 14, 22, 9, 6, 1
-    ];
-
-    pub fn keccak(lanes: &mut [u64; 25]) {
-        let mut c = [0u64; 5];
-        let (mut x, mut y): (usize, usize);
-        
-        for round in 0..12 {
-            // θ
-            FOR5!(x, 1, {
-                c[x] = lanes[x] ^ lanes[x+5] ^ lanes[x+10] ^ lanes[x+15] ^ lanes[x+20];
-            });
-            
-            FOR5!(x, 1, {
-                FOR5!(y, 5, {
-                    lanes[x + y] ^= c[(x+4)%5] ^ c[(x+1)%5].rotate_left(1);
-                });
-            });
-            
-            // ρ and π
-            let mut a = lanes[1];
-            x = 0;
-            REPEAT24!({
-                c[0] = lanes[PI[x]];
-                lanes[PI[x]] = a.rotate_left(RHO[x]);
-            }, {
-                a = c[0];
-                x += 1;
-            });
-            
-            // χ
-            FOR5!(y, 5, {
-                FOR5!(x, 1, {
-                    c[x] = lanes[x + y];
-                });
-                FOR5!(x, 1, {
-                    lanes[x + y] = c[x] ^((!c[(x+1) % 5]) & c[(x+2)%5]);
-                });
-            });
-            
-            // ι
-            lanes[0] ^= RC[round];
-        }
-    }
 }
 
 fn read_u64(bytes: &[u8; 8]) -> u64 {
-    unsafe{ *(bytes as *const [u8; 8] as *const u64) }.to_le()
+    u64::from_le_bytes(*bytes)
 }
 fn write_u64(val: u64) -> [u8; 8] {
-    unsafe{ *(&val.to_le() as *const u64 as *const [u8; 8]) }
+    val.to_le_bytes()
 }
 
-fn keccak(state: &mut [u8; 200]) {
-    let mut lanes = [0u64; 25];
-    let mut y;
-    for x in 0..5 {
-        FOR5!(y, 5, {
-            lanes[x + y] = read_u64(array_ref!(state, 8*(x+y), 8));
-        });
-    }
-    lanes::keccak(&mut lanes);
-    for x in 0..5 {
-        FOR5!(y, 5, {
-            let i = 8*(x+y);
-            state[i..i+8].copy_from_slice(&write_u64(lanes[x + y]));
-        });
+/// Absorb `bytes` into `state` starting at byte `pos` of the current block,
+/// returning the new position. Bytes are XORed in one at a time.
+fn absorb_bytes(state: &mut [u64; 25], mut pos: usize, bytes: &[u8], max_block_size: usize) -> usize {
+    for &b in bytes {
+        state[pos / 8] ^= (b as u64) << (8 * (pos % 8));
+        pos += 1;
+        if pos == max_block_size {
+            keccak_p1600(state, 12);
+            pos = 0;
+        }
     }
+    pos
 }
 
-pub fn k12s<T: AsRef<[u8]>, O: AsMut<[u8]>+Default>(input: T) -> O {
+/// KangarooTwelve with the given `customization` string, with output length
+/// determined by the type `O`.
+/// 
+/// Messages fitting in a single chunk (8192 bytes including the
+/// customization and its encoded length) are hashed directly; longer ones
+/// use the tree mode of `kangaroo_twelve`.
+pub fn k12s<T: AsRef<[u8]>, C: AsRef<[u8]>, O: AsMut<[u8]>+Default>(input: T, customization: C) -> O {
     let input = input.as_ref();
-    let mut state = [0u8; 200];
+    let customization = customization.as_ref();
+    let mut output = O::default();
+    
+    let mut buf = [0u8; 9];
+    let encoded_len = right_encode(customization.len(), &mut buf);
+    if input.len() + customization.len() + encoded_len.len() > 8192 {
+        kangaroo_twelve_into(input, customization, output.as_mut());
+        return output;
+    }
+    
+    let mut state = [0u64; 25];
     let max_block_size = 1344 / 8;  // r, also known as rate in bytes
     
     // === Absorb all the input blocks ===
-    // We unroll first loop, which allows simple copy
-    let mut block_size = min(input.len(), max_block_size);
-    state[0..block_size].copy_from_slice(&input[0..block_size]);
-    
-    let mut offset = block_size;
-    while offset < input.len() {
-        keccak(&mut state);
-        block_size = min(input.len() - offset, max_block_size);
-        for i in 0..block_size {
-            // TODO: is this sufficiently optimisable or better to convert to u64 first?
-            state[i] ^= input[i+offset];
+    // Whole blocks and whole lanes are XORed in as little-endian words
+    let mut offset = 0;
+    while input.len() - offset >= max_block_size {
+        for (i, lane) in state[0..max_block_size / 8].iter_mut().enumerate() {
+            *lane ^= read_u64(array_ref!(input, offset + 8*i, 8));
         }
-        offset += block_size;
+        keccak_p1600(&mut state, 12);
+        offset += max_block_size;
     }
+    let block_size = input.len() - offset;
+    let mut i = 0;
+    while i + 8 <= block_size {
+        state[i / 8] ^= read_u64(array_ref!(input, offset + i, 8));
+        i += 8;
+    }
+    let mut last = [0u8; 8];
+    last[0..block_size - i].copy_from_slice(&input[offset + i..]);
+    state[i / 8] ^= read_u64(&last);
+    
+    // === Absorb the customization string and its length ===
+    let pos = absorb_bytes(&mut state, block_size, customization, max_block_size);
+    let pos = absorb_bytes(&mut state, pos, encoded_len, max_block_size);
     
     // === Do the padding and switch to the squeezing phase ===
-    state[block_size] ^= 0x07;
-    state[max_block_size-1] ^= 0x80;
-    keccak(&mut state);
+    state[pos / 8] ^= 0x07 << (8 * (pos % 8));
+    state[max_block_size / 8 - 1] ^= 0x80 << 56;
+    keccak_p1600(&mut state, 12);
     
     // === Squeeze out all the output blocks ===
-    let mut output = O::default();
     {
         offset = 0;
         let output_ref = output.as_mut();
         let mut output_len = output_ref.len();
         loop {
-            block_size = min(output_len, max_block_size);
-            output_ref[offset..(offset+block_size)].copy_from_slice(&state[0..block_size]);
+            let block_size = min(output_len, max_block_size);
+            for (i, &lane) in state[0..block_size.div_ceil(8)].iter().enumerate() {
+                let bytes = write_u64(lane);
+                let n = min(8, block_size - 8 * i);
+                output_ref[offset + 8*i .. offset + 8*i + n].copy_from_slice(&bytes[0..n]);
+            }
             output_len -= block_size;
             offset += block_size;
             if output_len == 0 {
                 break;
             }
-            keccak(&mut state);
+            keccak_p1600(&mut state, 12);
         }
     }
     output
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k12::kangaroo_twelve;
+    
+    #[test]
+    fn matches_k12() {
+        let m: Vec<u8> = (0..65536).map(|j| (j % 251) as u8).collect();
+        let mut x = 0x0123456789abcdefu64;
+        let mut lengths = vec![0, 1, 167, 168, 169, 336, 8189, 8190, 8191, 8192, 65536];
+        for _ in 0..50 {
+            x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            lengths.push((x >> 32) as usize % 65537);
+        }
+        for &len in &lengths {
+            let out: [u8; 32] = k12s(&m[0..len], "");
+            assert_eq!(out[..], kangaroo_twelve(&m[0..len], "", 32)[..], "len {}", len);
+        }
+        
+        // with customization, either side of the chunk boundary
+        for &(len, c_len) in &[(0, 3), (100, 300), (8000, 190), (8000, 191), (8192, 1)] {
+            let c = &m[1000..1000 + c_len];
+            let out: [u8; 32] = k12s(&m[0..len], c);
+            assert_eq!(out[..], kangaroo_twelve(&m[0..len], c, 32)[..], "len {}, {}", len, c_len);
+        }
+    }
+    
+    #[test]
+    fn whole_blocks() {
+        // M, or S = M || right_encode(0), filling whole 168-byte blocks: the
+        // last full block must be permuted before the padding goes in
+        let m: Vec<u8> = (0..8192).map(|j| (j % 251) as u8).collect();
+        for &len in &[167, 168, 335, 336, 503, 504, 8063, 8064] {
+            let out: [u8; 32] = k12s(&m[0..len], "");
+            assert_eq!(out[..], kangaroo_twelve(&m[0..len], "", 32)[..], "len {}", len);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+// To the extent possible under law, the implementer has waived all copyright
+// and related or neighboring rights to the source code in this file.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! The Keccak permutation.
+//! 
+//! The state is 25 64-bit lanes, lane `x + 5*y` holding bits `64*(x + 5*y)`
+//! onwards of the state in little-endian order.
+//! 
+//! `keccak_f1600` is the full 24-round permutation used by SHA-3;
+//! `keccak_p1600` takes the number of rounds, 1 to 24, as K12 (12) and M14
+//! (14) use fewer. `keccak_p1600_x4` permutes four interleaved states at once.
+
+pub use keccak_p::{keccak_f1600, keccak_p1600, keccak_p1600_x4};
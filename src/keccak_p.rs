@@ -0,0 +1,359 @@
+// Keccak-p[1600, n_r] permutation, as specified in FIPS 202:
+// https://nvlpubs.nist.gov/nistpubs/FIPS/NIST.FIPS.202.pdf
+//
+// Some optimisations copied from https://github.com/RustCrypto/hashes/tree/master/sha3/src
+//
+// To the extent possible under law, the implementer has waived all copyright
+// and related or neighboring rights to the source code in this file.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+//! The Keccak-p[1600, n_r] permutation, used by K12, M14, TurboSHAKE and the
+//! duplex (12 or 14 rounds) and by SHA-3 (24 rounds, Keccak-f[1600]).
+//! 
+//! The state is 25 64-bit lanes, lane `x + 5*y` holding bits `64*(x + 5*y)`
+//! onwards of the state in little-endian order. See the `keccak` module for
+//! the public interface.
+
+#[macro_use]
+mod macros {
+    macro_rules! REPEAT4 {
+        ($e: expr) => ( $e; $e; $e; $e; )
+    }
+
+    macro_rules! REPEAT5 {
+        ($e: expr) => ( $e; $e; $e; $e; $e; )
+    }
+
+    macro_rules! REPEAT6 {
+        ($e: expr) => ( $e; $e; $e; $e; $e; $e; )
+    }
+
+    macro_rules! REPEAT24 {
+        ($e: expr, $s: expr) => (
+            REPEAT6!({ $e; $s; });
+            REPEAT6!({ $e; $s; });
+            REPEAT6!({ $e; $s; });
+            REPEAT5!({ $e; $s; });
+            $e;
+        )
+    }
+
+    macro_rules! FOR5 {
+        ($v: expr, $s: expr, $e: expr) => {
+            $v = 0;
+            REPEAT4!({
+                $e;
+                $v += $s;
+            });
+            $e;
+        }
+    }
+}
+
+/// Round constants of Keccak-f[1600]; Keccak-p with `n` rounds uses the
+/// last `n` of these.
+const RC: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808a,
+    0x8000000080008000,
+    0x000000000000808b,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008a,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000a,
+    0x000000008000808b,
+    0x800000000000008b,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800a,
+    0x800000008000000a,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+// (0..24).map(|t| ((t+1)*(t+2)/2) % 64)
+const RHO: [u32; 24] = [
+    1, 3, 6, 10, 15, 21,28, 36, 45, 55, 2, 14, 27,
+    41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44
+];
+const PI: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23,
+    19, 13, 12, 2, 20, 14, 22, 9, 6, 1
+];
+
+/// Keccak-f\[1600\]: the full 24-round permutation
+pub fn keccak_f1600(lanes: &mut [u64; 25]) {
+    keccak_p1600(lanes, 24)
+}
+
+/// Keccak-p[1600, n_r] with `n_r = rounds`: the last `rounds` rounds of
+/// Keccak-f[1600].
+/// 
+/// Panics unless `1 <= rounds <= 24`.
+pub fn keccak_p1600(lanes: &mut [u64; 25], rounds: usize) {
+    assert!((1..=24).contains(&rounds), "Keccak-p rounds out of range");
+    let mut c = [0u64; 5];
+    let (mut x, mut y): (usize, usize);
+    
+    for rc in &RC[24 - rounds..] {
+        // θ
+        FOR5!(x, 1, {
+            c[x] = lanes[x] ^ lanes[x+5] ^ lanes[x+10] ^ lanes[x+15] ^ lanes[x+20];
+        });
+        
+        FOR5!(x, 1, {
+            FOR5!(y, 5, {
+                lanes[x + y] ^= c[(x+4)%5] ^ c[(x+1)%5].rotate_left(1);
+            });
+        });
+        
+        // ρ and π
+        let mut a = lanes[1];
+        x = 0;
+        REPEAT24!({
+            c[0] = lanes[PI[x]];
+            lanes[PI[x]] = a.rotate_left(RHO[x]);
+        }, {
+            a = c[0];
+            x += 1;
+        });
+        
+        // χ
+        FOR5!(y, 5, {
+            FOR5!(x, 1, {
+                c[x] = lanes[x + y];
+            });
+            FOR5!(x, 1, {
+                lanes[x + y] = c[x] ^((!c[(x+1) % 5]) & c[(x+2)%5]);
+            });
+        });
+        
+        // ι
+        lanes[0] ^= rc;
+    }
+}
+
+/// Apply `keccak_p1600` to four independent states at once.
+/// 
+/// Lane `i` of state `k` is `lanes[i][k]`. This uses AVX2 where available.
+/// 
+/// Panics unless `1 <= rounds <= 24`.
+pub fn keccak_p1600_x4(lanes: &mut [[u64; 4]; 25], rounds: usize) {
+    assert!((1..=24).contains(&rounds), "Keccak-p rounds out of range");
+    select_keccak_p1600_x4()(lanes, rounds)
+}
+
+/// Pick the implementation of `keccak_p1600_x4` for this CPU, for callers
+/// which permute many times and should detect CPU features only once.
+/// 
+/// The returned function does not check `rounds`.
+pub fn select_keccak_p1600_x4() -> fn(&mut [[u64; 4]; 25], usize) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        if is_x86_feature_detected!("avx2") {
+            return keccak_p1600_x4_avx2;
+        }
+    }
+    keccak_p1600_x4_portable
+}
+
+/// Only returned by `select_keccak_p1600_x4` when AVX2 is available
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn keccak_p1600_x4_avx2(lanes: &mut [[u64; 4]; 25], rounds: usize) {
+    unsafe { avx2::keccak_p1600_x4(lanes, rounds) }
+}
+
+/// Interleaved scalar implementation of `keccak_p1600_x4`
+fn keccak_p1600_x4_portable(lanes: &mut [[u64; 4]; 25], rounds: usize) {
+    let mut c = [[0u64; 4]; 5];
+    
+    for &rc in &RC[24 - rounds..] {
+        // θ
+        for x in 0..5 {
+            for k in 0..4 {
+                c[x][k] = lanes[x][k] ^ lanes[x+5][k] ^ lanes[x+10][k] ^
+                        lanes[x+15][k] ^ lanes[x+20][k];
+            }
+        }
+        for x in 0..5 {
+            for k in 0..4 {
+                let d = c[(x+4)%5][k] ^ c[(x+1)%5][k].rotate_left(1);
+                for y in 0..5 {
+                    lanes[x + 5*y][k] ^= d;
+                }
+            }
+        }
+        
+        // ρ and π
+        let mut a = lanes[1];
+        for x in 0..24 {
+            let t = lanes[PI[x]];
+            for k in 0..4 {
+                lanes[PI[x]][k] = a[k].rotate_left(RHO[x]);
+            }
+            a = t;
+        }
+        
+        // χ
+        for y in 0..5 {
+            let row = [lanes[5*y], lanes[5*y+1], lanes[5*y+2], lanes[5*y+3], lanes[5*y+4]];
+            for x in 0..5 {
+                for k in 0..4 {
+                    lanes[5*y + x][k] = row[x][k] ^ ((!row[(x+1)%5][k]) & row[(x+2)%5][k]);
+                }
+            }
+        }
+        
+        // ι
+        for lane in lanes[0].iter_mut() {
+            *lane ^= rc;
+        }
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod avx2 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+    
+    use super::{RC, RHO, PI};
+    
+    #[target_feature(enable = "avx2")]
+    #[inline]
+    unsafe fn rotate_left(x: __m256i, n: u32) -> __m256i {
+        _mm256_or_si256(_mm256_sllv_epi64(x, _mm256_set1_epi64x(n as i64)),
+                _mm256_srlv_epi64(x, _mm256_set1_epi64x(64 - n as i64)))
+    }
+    
+    /// AVX2 implementation of `keccak_p1600_x4`: one 256-bit register per lane
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn keccak_p1600_x4(state: &mut [[u64; 4]; 25], rounds: usize) {
+        let mut lanes = [_mm256_setzero_si256(); 25];
+        for (lane, s) in lanes.iter_mut().zip(state.iter()) {
+            *lane = _mm256_loadu_si256(s.as_ptr() as *const __m256i);
+        }
+        let mut c = [_mm256_setzero_si256(); 5];
+        
+        for &rc in &RC[24 - rounds..] {
+            // θ
+            for x in 0..5 {
+                c[x] = _mm256_xor_si256(_mm256_xor_si256(lanes[x], lanes[x+5]),
+                        _mm256_xor_si256(_mm256_xor_si256(lanes[x+10], lanes[x+15]),
+                                lanes[x+20]));
+            }
+            for x in 0..5 {
+                let d = _mm256_xor_si256(c[(x+4)%5], rotate_left(c[(x+1)%5], 1));
+                for y in 0..5 {
+                    lanes[x + 5*y] = _mm256_xor_si256(lanes[x + 5*y], d);
+                }
+            }
+            
+            // ρ and π
+            let mut a = lanes[1];
+            for x in 0..24 {
+                let t = lanes[PI[x]];
+                lanes[PI[x]] = rotate_left(a, RHO[x]);
+                a = t;
+            }
+            
+            // χ
+            for y in 0..5 {
+                let row = [lanes[5*y], lanes[5*y+1], lanes[5*y+2], lanes[5*y+3], lanes[5*y+4]];
+                for x in 0..5 {
+                    lanes[5*y + x] = _mm256_xor_si256(row[x],
+                            _mm256_andnot_si256(row[(x+1)%5], row[(x+2)%5]));
+                }
+            }
+            
+            // ι
+            lanes[0] = _mm256_xor_si256(lanes[0], _mm256_set1_epi64x(rc as i64));
+        }
+        
+        for (s, lane) in state.iter_mut().zip(lanes.iter()) {
+            _mm256_storeu_si256(s.as_mut_ptr() as *mut __m256i, *lane);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    #[test]
+    fn keccak_f1600() {
+        // Source: KeccakF-1600-IntermediateValues.txt, Keccak team
+        let expected = [[
+                0xF1258F7940E1DDE7, 0x84D5CCF933C0478A, 0xD598261EA65AA9EE, 0xBD1547306F80494D,
+                0x8B284E056253D057, 0xFF97A42D7F8E6FD4, 0x90FEE5A0A44647C4, 0x8C5BDA0CD6192E76,
+                0xAD30A6F71B19059C, 0x30935AB7D08FFC64, 0xEB5AA93F2317D635, 0xA9A6E6260D712103,
+                0x81A57C16DBCF555F, 0x43B831CD0347C826, 0x01F22F1A11A5569F, 0x05E5635A21D9AE61,
+                0x64BEFEF28CC970F2, 0x613670957BC46611, 0xB87C5A554FD00ECB, 0x8C3EE88A1CCF32C8,
+                0x940C7922AE3A2614, 0x1841F924A2C509E4, 0x16F53526E70465C2, 0x75F644E97F30A13B,
+                0xEAF1FF7B5CECA249,
+            ], [
+                0x2D5C954DF96ECB3C, 0x6A332CD07057B56D, 0x093D8D1270D76B6C, 0x8A20D9B25569D094,
+                0x4F9C4F99E5E7F156, 0xF957B9A2DA65FB38, 0x85773DAE1275AF0D, 0xFAF4F247C3D810F7,
+                0x1F1B9EE6F79A8759, 0xE4FECC0FEE98B425, 0x68CE61B6B9CE68A1, 0xDEEA66C4BA8F974F,
+                0x33C43D836EAFB1F5, 0xE00654042719DBD9, 0x7CF8A9F009831265, 0xFD5449A6BF174743,
+                0x97DDAD33D8994B40, 0x48EAD5FC5D0BE774, 0xE3B8C8EE55B7B03C, 0x91A0226E649E42E9,
+                0x900E3129E7BADD7B, 0x202A9EC5FAA3CCE8, 0x5B3402464E1C3DB6, 0x609F4E62A44C1059,
+                0x20D06CD26A8FBF5C,
+            ]];
+        let mut state = [0u64; 25];
+        for expected in expected.iter() {
+            super::keccak_f1600(&mut state);
+            assert_eq!(&state, expected);
+        }
+    }
+    
+    #[test]
+    #[should_panic]
+    fn zero_rounds() {
+        keccak_p1600(&mut [0; 25], 0);
+    }
+    
+    #[test]
+    fn keccak_p1600_x4() {
+        let mut x = 0x0123456789abcdefu64;
+        let mut states = [[0u64; 25]; 4];
+        for state in states.iter_mut() {
+            for lane in state.iter_mut() {
+                x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                *lane = x;
+            }
+        }
+        let mut interleaved = [[0u64; 4]; 25];
+        for i in 0..25 {
+            for k in 0..4 {
+                interleaved[i][k] = states[k][i];
+            }
+        }
+        for &rounds in &[1, 12, 14, 24] {
+            let mut states = states;
+            for state in states.iter_mut() {
+                keccak_p1600(state, rounds);
+            }
+            
+            let mut portable = interleaved;
+            let mut interleaved = interleaved;
+            keccak_p1600_x4_portable(&mut portable, rounds);
+            super::keccak_p1600_x4(&mut interleaved, rounds);
+            for i in 0..25 {
+                for k in 0..4 {
+                    assert_eq!(portable[i][k], states[k][i]);
+                    assert_eq!(interleaved[i][k], states[k][i]);
+                }
+            }
+        }
+    }
+}
@@ -5,26 +5,43 @@ extern crate keccak_hash;
 extern crate sha2;
 extern crate sha3;
 extern crate generic_array;
+#[cfg(test)]
+extern crate digest;
 
 pub use metrohash::{MetroHash64, MetroHash128};
 
 pub use seahash::SeaHasher;
 pub use seahash::State as SeaHash;
 
-pub use highwayhash::HighwayHash;
+pub use highwayhash::{HighwayHash, HighwayBuildHasher};
+pub use highway_mac::{HighwayMac, TagLen};
+pub use duplex::Duplex;
 
 // pub use tiny_keccak::{Keccak, keccak256};
 pub use keccak_hash::{H256, keccak};
 
-pub use k12::kangaroo_twelve;
+pub use k12::{kangaroo_twelve, kangaroo_twelve_into, KangarooTwelve, KangarooTwelveReader};
+pub use k12::{kangaroo_twelve_256, kangaroo_twelve_256_into, KangarooTwelve256, m14};
+pub use k12::{TurboShake128, TurboShake256, TurboShakeReader};
 pub use k12_simplified::k12s;
+pub use sponge::SpongeReader;
 
 pub use sha2::{Digest};
 pub use generic_array::{GenericArray, typenum};
 
+pub mod bench;
+pub mod fips202;
+pub mod keccak;
+pub mod sp800_185;
+
+mod duplex;
 mod highwayhash;
+mod highway_mac;
 mod k12;
 mod k12_simplified;
+mod keccak_p;
+mod sponge;
+mod util;
 
 pub fn sha512_trunc256(input: &[u8]) -> GenericArray<u8, typenum::U32> {
     let mut hasher = sha2::Sha512Trunc256::default();
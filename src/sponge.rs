@@ -0,0 +1,173 @@
+// Keccak sponge construction
+//
+// Some optimisations copied from https://github.com/RustCrypto/hashes/tree/master/sha3/src
+//
+// To the extent possible under law, the implementer has waived all copyright
+// and related or neighboring rights to the source code in this file.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+use std::cmp::min;
+use std::io;
+
+use keccak_p::keccak_p1600;
+
+#[macro_use]
+mod macros {
+    /// Copied from `arrayref` crate
+    macro_rules! array_ref {
+        ($arr:expr, $offset:expr, $len:expr) => {{
+            {
+                #[inline]
+                unsafe fn as_array<T>(slice: &[T]) -> &[T; $len] {
+                    &*(slice.as_ptr() as *const [_; $len])
+                }
+                let offset = $offset;
+                let slice = & $arr[offset..offset + $len];
+                unsafe {
+                    as_array(slice)
+                }
+            }
+        }}
+    }
+}
+
+fn read_u64(bytes: &[u8; 8]) -> u64 {
+    u64::from_le_bytes(*bytes)
+}
+fn write_u64(val: u64) -> [u8; 8] {
+    val.to_le_bytes()
+}
+
+/// Keccak-p[1600] sponge, as used by K12, M14, TurboSHAKE and SHA-3.
+/// 
+/// Input is absorbed with `absorb`, then after `pad` output may be read with
+/// `squeeze`. The state is kept as 25 lanes; input is XORed in as
+/// little-endian words.
+#[derive(Clone)]
+pub struct Sponge {
+    lanes: [u64; 25],
+    // bytes of the current block absorbed or squeezed
+    pos: usize,
+    // rate in bytes; a multiple of 8
+    rate: usize,
+    rounds: usize,
+}
+
+impl Sponge {
+    /// Create an empty sponge; `rate` is in bytes and must be a multiple of 8
+    pub fn new(rate: usize, rounds: usize) -> Self {
+        Sponge { lanes: [0; 25], pos: 0, rate, rounds }
+    }
+    
+    /// Rate in bytes
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+    
+    #[inline]
+    fn xor_byte(&mut self, pos: usize, byte: u8) {
+        self.lanes[pos / 8] ^= (byte as u64) << (8 * (pos % 8));
+    }
+    
+    /// Absorb `input`; may be called repeatedly before `pad`
+    pub fn absorb(&mut self, mut input: &[u8]) {
+        while !input.is_empty() {
+            if self.pos.is_multiple_of(8) && input.len() >= 8 {
+                // whole lanes
+                let n = min(input.len(), self.rate - self.pos) / 8;
+                let first = self.pos / 8;
+                for i in 0..n {
+                    self.lanes[first + i] ^= read_u64(array_ref!(input, 8 * i, 8));
+                }
+                self.pos += 8 * n;
+                input = &input[8 * n..];
+            } else {
+                let pos = self.pos;
+                self.xor_byte(pos, input[0]);
+                self.pos += 1;
+                input = &input[1..];
+            }
+            if self.pos == self.rate {
+                keccak_p1600(&mut self.lanes, self.rounds);
+                self.pos = 0;
+            }
+        }
+    }
+    
+    /// Absorb zeros up to the end of the current block, if any of it has
+    /// been used
+    pub fn fill_block(&mut self) {
+        if self.pos != 0 {
+            keccak_p1600(&mut self.lanes, self.rounds);
+            self.pos = 0;
+        }
+    }
+    
+    /// Pad with the given domain separation `suffix` and switch to squeezing
+    pub fn pad(&mut self, suffix: u8) {
+        let pos = self.pos;
+        self.xor_byte(pos, suffix);
+        if ((suffix & 0x80) != 0) && (pos == (self.rate-1)) {
+            // The suffix includes the first bit of the padding; if this
+            // collides with the final bit we need another block.
+            keccak_p1600(&mut self.lanes, self.rounds);
+        }
+        let last = self.rate - 1;
+        self.xor_byte(last, 0x80);
+        keccak_p1600(&mut self.lanes, self.rounds);
+        self.pos = 0;
+    }
+    
+    /// Read the next `output.len()` bytes of output
+    pub fn squeeze(&mut self, mut output: &mut [u8]) {
+        while !output.is_empty() {
+            if self.pos == self.rate {
+                keccak_p1600(&mut self.lanes, self.rounds);
+                self.pos = 0;
+            }
+            if self.pos.is_multiple_of(8) && output.len() >= 8 {
+                // whole lanes
+                let n = min(output.len(), self.rate - self.pos) / 8;
+                let first = self.pos / 8;
+                for i in 0..n {
+                    output[8 * i .. 8 * i + 8].copy_from_slice(&write_u64(self.lanes[first + i]));
+                }
+                self.pos += 8 * n;
+                output = &mut {output}[8 * n..];
+            } else {
+                output[0] = (self.lanes[self.pos / 8] >> (8 * (self.pos % 8))) as u8;
+                self.pos += 1;
+                output = &mut {output}[1..];
+            }
+        }
+    }
+}
+
+/// Extendable output read from a padded `Sponge`.
+/// 
+/// Reading output in several pieces gives the same result as reading it all
+/// at once.
+#[derive(Clone)]
+pub struct SpongeReader {
+    sponge: Sponge,
+}
+
+impl SpongeReader {
+    /// Read output from `sponge`, which must already be padded
+    pub fn new(sponge: Sponge) -> Self {
+        SpongeReader { sponge }
+    }
+    
+    /// Fill `output` with the next `output.len()` bytes of hash
+    pub fn squeeze(&mut self, output: &mut [u8]) {
+        self.sponge.squeeze(output);
+    }
+}
+
+/// Never fails and always fills the whole buffer.
+impl io::Read for SpongeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.squeeze(buf);
+        Ok(buf.len())
+    }
+}
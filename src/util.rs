@@ -0,0 +1,45 @@
+// Small helpers shared between modules
+//
+// To the extent possible under law, the implementer has waived all copyright
+// and related or neighboring rights to the source code in this file.
+// http://creativecommons.org/publicdomain/zero/1.0/
+
+use std::hint::black_box;
+
+/// Compare `a` and `b` in time independent of their contents (but not of
+/// their lengths, which are not treated as secret).
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b) {
+        diff |= x ^ y;
+    }
+    // don't let the optimiser introduce an early exit
+    black_box(diff) == 0
+}
+
+/// Parse hex digits in pairs, ignoring spaces and line breaks
+#[cfg(test)]
+pub fn read_bytes<T: AsRef<[u8]>>(s: T) -> Vec<u8> {
+    fn b(c: u8) -> u8 {
+        match c {
+            b'0' ..= b'9' => c - b'0',
+            b'a' ..= b'f' => c - b'a' + 10,
+            b'A' ..= b'F' => c - b'A' + 10,
+            _ => unreachable!(),
+        }
+    }
+    let s = s.as_ref();
+    let mut i = 0;
+    let mut v = Vec::new();
+    while i < s.len() {
+        if s[i] == b' ' || s[i] == b'\n' { i += 1; continue; }
+        
+        let n = b(s[i]) * 16 + b(s[i+1]);
+        v.push(n);
+        i += 2;
+    }
+    v
+}
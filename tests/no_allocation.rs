@@ -0,0 +1,48 @@
+// Checks that kangaroo_twelve_into does not allocate. This installs a
+// counting global allocator, so it lives in its own test binary.
+
+extern crate hash_bench;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use hash_bench::{kangaroo_twelve, kangaroo_twelve_into};
+
+/// Counts allocations made by the current thread
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+#[test]
+fn no_allocation() {
+    let m: Vec<u8> = (0..100_000).map(|j| (j % 251) as u8).collect();
+    let c: Vec<u8> = (0..300).map(|j| j as u8).collect();
+    let mut output = [0u8; 500];
+    for &len in &[0, 100, 8191, 8192, 8193, 5 * 8192, 100_000] {
+        let before = ALLOCATIONS.with(|n| n.get());
+        kangaroo_twelve_into(&m[0..len], &c[..], &mut output);
+        assert_eq!(ALLOCATIONS.with(|n| n.get()), before, "len {}", len);
+        assert_eq!(&output[..], &kangaroo_twelve(&m[0..len], &c[..], 500)[..]);
+    }
+    
+    // check the counter works
+    let before = ALLOCATIONS.with(|n| n.get());
+    kangaroo_twelve(&m[0..10], "", 32);
+    assert!(ALLOCATIONS.with(|n| n.get()) > before);
+}